@@ -0,0 +1,70 @@
+#!/bin/sh -e
+# Measures the strength of every skill level by matches between neighbouring levels, each
+# level searching with exactly the node and depth limits it plays with, then full strength
+# against level 19. Full strength searches the nodes the curve of Skill::limit in
+# src/skill.rs would give level 20, which is also the match limit and so above the limit
+# of every lower level. Games start from scripts/openings.epd, every position after one
+# move each in shuffled order.
+#
+# The match results are written to scripts/skill-calibration.txt, and the Skill Level
+# table of src/skill.rs built from them is printed at the end, with the error of each
+# level summed up the chain and any neighbours not clearly apart
+#
+# Usage: scripts/calibrate-skill.sh [games per match]
+
+games=${1:-300}
+output=scripts/skill-calibration.txt
+
+# 40 * 1.5^20, update with the curve of Skill::limit
+nodes=133009
+
+cargo build -r
+
+play() {
+    printf '%s vs %s: ' "$1" "$2" | tee -a "$output"
+
+    # Equal SPRT bounds never stop a match early
+    ./target/release/reggz match "$1" "$2" "games=$games" "nodes=$nodes" \
+        openings=scripts/openings.epd elo0=0 elo1=0 | tail -n 1 | tee -a "$output"
+}
+
+: > "$output"
+
+for level in $(seq 1 19); do
+    play "self:skill=$level" "self:skill=$((level - 1))"
+done
+
+play self self:skill=19
+
+# The Elo of each level for src/skill.rs, summed up the chain of matches with level 0 set
+# at 800, as there is no rated opponent to anchor the scale to. A level is clearly above
+# the one below when the Elo of their match is more than its error
+awk '
+{
+    split($1, first, "=")
+    level = $1 == "self" ? 20 : first[2]
+
+    for (i = 1; i < NF; i++) {
+        if ($i == "elo") {
+            elo = $(i + 1)
+            error = $(i + 3)
+        }
+    }
+
+    levels[level] = levels[level - 1] + elo
+    variance[level] = variance[level - 1] + error * error
+
+    if (elo <= error) {
+        tied = tied " " level
+    }
+}
+
+END {
+    for (level = 0; level <= 20; level++) {
+        printf "    (%d.0, %d), // +- %d\n", level, 800 + levels[level] + 0.5, sqrt(variance[level]) + 0.5
+    }
+
+    if (tied != "") {
+        print "Not clearly above the level below:" tied
+    }
+}' "$output"
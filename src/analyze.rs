@@ -0,0 +1,447 @@
+use crate::pgn::{self, Game};
+use crate::search::{Node, Searcher, MATE, MATE_MAX_PLIES};
+use crate::timer;
+use shakmaty::san::SanPlus;
+use shakmaty::zobrist::Zobrist64;
+use shakmaty::{Chess, Color, EnPassantMode, Move, Position};
+use std::fs;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+const TRANSPOSITION_TABLE_SIZE_MB: usize = 64;
+const TRANSPOSITION_TABLE_LENGTH: usize =
+    TRANSPOSITION_TABLE_SIZE_MB * 1_000_000 / size_of::<Option<Node>>();
+
+// Drops in winning chances, in percent, from which a move is an inaccuracy, a mistake or a blunder
+const INACCURACY: f64 = 10.0;
+const MISTAKE: f64 = 20.0;
+const BLUNDER: f64 = 30.0;
+
+// Plies of the best line given as a variation
+const VARIATION_PLIES: usize = 8;
+
+enum Limit {
+    MoveTime(u64),
+    Nodes(u64),
+    Depth(i16),
+}
+
+struct Analysis {
+    // Score for the side to move, none when the game is over
+    score: Option<i16>,
+    best_move: Option<Move>,
+    principal_variation: Vec<Move>,
+    game_over_score: i16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Judgement {
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Judgement {
+    fn from_drop(drop: f64) -> Option<Judgement> {
+        if drop >= BLUNDER {
+            Some(Judgement::Blunder)
+        } else if drop >= MISTAKE {
+            Some(Judgement::Mistake)
+        } else if drop >= INACCURACY {
+            Some(Judgement::Inaccuracy)
+        } else {
+            None
+        }
+    }
+
+    fn nag(self) -> &'static str {
+        match self {
+            Judgement::Inaccuracy => "$6",
+            Judgement::Mistake => "$2",
+            Judgement::Blunder => "$4",
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Judgement::Inaccuracy => "Inaccuracy",
+            Judgement::Mistake => "Mistake",
+            Judgement::Blunder => "Blunder",
+        }
+    }
+}
+
+#[derive(Default)]
+struct PlayerSummary {
+    moves: u32,
+    accuracy: f64,
+    inaccuracies: u32,
+    mistakes: u32,
+    blunders: u32,
+}
+
+/// Usage: analyze <pgn> <output> [movetime|nodes|depth] [value]
+///
+/// Searches every position of each game and writes the games back with `[%eval]` comments,
+/// `?!`, `?` and `??` NAGs for drops in winning chances, the best line after mistakes and
+/// the accuracy of each player.
+pub fn run(args: &[String]) {
+    let (Some(pgn_path), Some(output_path)) = (args.first(), args.get(1)) else {
+        eprintln!("usage: reggz analyze <pgn> <output> [movetime|nodes|depth] [value]");
+        return;
+    };
+
+    let value = args.get(3).and_then(|v| v.parse::<u64>().ok());
+
+    let limit = match (args.get(2).map(String::as_str), value) {
+        (None, _) => Limit::MoveTime(1000),
+        (Some("movetime"), Some(value)) => Limit::MoveTime(value),
+        (Some("nodes"), Some(value)) => Limit::Nodes(value),
+        (Some("depth"), Some(value)) => Limit::Depth(value.min(i16::MAX as u64) as i16),
+        _ => {
+            eprintln!("usage: reggz analyze <pgn> <output> [movetime|nodes|depth] [value]");
+            return;
+        }
+    };
+
+    let text = match fs::read_to_string(pgn_path) {
+        Ok(text) => text,
+        Err(error) => {
+            eprintln!("failed to read {pgn_path}: {error}");
+            return;
+        }
+    };
+
+    let games = pgn::read_games(&text);
+
+    let mut transposition_table = Arc::new(Mutex::new(vec![None; TRANSPOSITION_TABLE_LENGTH]));
+
+    let mut output = String::new();
+
+    for (game_number, game) in games.iter().enumerate() {
+        let Some((start, moves)) = game.play() else {
+            eprintln!("game {}: invalid starting position", game_number + 1);
+            continue;
+        };
+
+        if moves.len() < game.moves.len() {
+            eprintln!(
+                "game {}: illegal move {}, analyzing up to it",
+                game_number + 1,
+                game.moves[moves.len()]
+            );
+        }
+
+        for node in transposition_table.lock().unwrap().iter_mut() {
+            *node = None;
+        }
+
+        let (annotated, summaries) =
+            annotate(game, &start, &moves, &limit, &mut transposition_table);
+
+        let white = game.header("White").unwrap_or("White");
+        let black = game.header("Black").unwrap_or("Black");
+
+        println!("game {}: {white} - {black}", game_number + 1);
+
+        for (name, summary) in [(white, &summaries[0]), (black, &summaries[1])] {
+            println!(
+                "  {name}: accuracy {:.1}%, {} inaccuracies, {} mistakes, {} blunders",
+                summary.accuracy, summary.inaccuracies, summary.mistakes, summary.blunders
+            );
+        }
+
+        output.push_str(&annotated);
+    }
+
+    if let Err(error) = fs::write(output_path, output) {
+        eprintln!("failed to write {output_path}: {error}");
+    }
+}
+
+// The annotated PGN of a game, and the summaries of white and black
+fn annotate(
+    game: &Game,
+    start: &Chess,
+    moves: &[Move],
+    limit: &Limit,
+    transposition_table: &mut Arc<Mutex<Vec<Option<Node>>>>,
+) -> (String, [PlayerSummary; 2]) {
+    let mut boards: Vec<Chess> = vec![start.clone()];
+    let mut position_history: Vec<Zobrist64> = vec![start.zobrist_hash(EnPassantMode::Legal)];
+    let mut analyses: Vec<Analysis> = Vec::with_capacity(moves.len() + 1);
+
+    for (i, m) in moves.iter().enumerate() {
+        analyses.push(analyze(
+            &boards[i],
+            &position_history,
+            limit,
+            transposition_table,
+        ));
+
+        let mut board = boards[i].clone();
+        board.play_unchecked(*m);
+
+        position_history.push(board.zobrist_hash(EnPassantMode::Legal));
+        boards.push(board);
+    }
+
+    analyses.push(analyze(
+        &boards[moves.len()],
+        &position_history,
+        limit,
+        transposition_table,
+    ));
+
+    let mut summaries = [PlayerSummary::default(), PlayerSummary::default()];
+    let mut annotations: Vec<String> = Vec::with_capacity(moves.len());
+
+    for (i, m) in moves.iter().enumerate() {
+        let board = &boards[i];
+        let before = &analyses[i];
+        let after = &analyses[i + 1];
+
+        let before_score = before.score.unwrap_or(before.game_over_score);
+        let after_score = -after.score.unwrap_or(after.game_over_score);
+
+        // Playing the best move loses nothing, whatever the search of the next position says
+        let drop = if before.best_move == Some(*m) {
+            0.0
+        } else {
+            (winning_chances(before_score) - winning_chances(after_score)).max(0.0)
+        };
+
+        let summary = &mut summaries[usize::from(board.turn().is_black())];
+
+        summary.moves += 1;
+        summary.accuracy += move_accuracy(drop);
+
+        let mut annotation = String::new();
+
+        let judgement = Judgement::from_drop(drop);
+
+        match judgement {
+            Some(Judgement::Blunder) => summary.blunders += 1,
+            Some(Judgement::Mistake) => summary.mistakes += 1,
+            Some(Judgement::Inaccuracy) => summary.inaccuracies += 1,
+            None => {}
+        }
+
+        if let Some(judgement) = judgement {
+            annotation.push_str(judgement.nag());
+            annotation.push(' ');
+        }
+
+        let eval = after.score.map(|score| white_eval(board.turn(), score));
+
+        let best_san = before
+            .best_move
+            .map(|best_move| SanPlus::from_move(board.clone(), best_move));
+
+        let comment = match (judgement, best_san) {
+            (Some(judgement), Some(best_san)) => {
+                Some(format!("{}. {best_san} was best.", judgement.name()))
+            }
+            _ => None,
+        };
+
+        match (eval, comment) {
+            (Some(eval), Some(comment)) => {
+                annotation.push_str(&format!("{{ [%eval {eval}] {comment} }}"))
+            }
+            (Some(eval), None) => annotation.push_str(&format!("{{ [%eval {eval}] }}")),
+            (None, Some(comment)) => annotation.push_str(&format!("{{ {comment} }}")),
+            (None, None) => {}
+        }
+
+        if judgement.is_some() && !before.principal_variation.is_empty() {
+            annotation.push_str(&format!(
+                " ({})",
+                variation(board, &before.principal_variation)
+            ));
+        }
+
+        annotations.push(annotation);
+    }
+
+    for summary in &mut summaries {
+        if summary.moves > 0 {
+            summary.accuracy /= summary.moves as f64;
+        }
+    }
+
+    let mut annotated_game = game.clone();
+
+    annotated_game.moves.truncate(moves.len());
+
+    annotated_game.headers.retain(|(name, _)| {
+        !["Annotator", "WhiteAccuracy", "BlackAccuracy"].contains(&name.as_str())
+    });
+
+    annotated_game
+        .headers
+        .push(("Annotator".to_string(), "Reggz".to_string()));
+
+    for (name, summary) in [
+        ("WhiteAccuracy", &summaries[0]),
+        ("BlackAccuracy", &summaries[1]),
+    ] {
+        annotated_game
+            .headers
+            .push((name.to_string(), format!("{:.1}", summary.accuracy)));
+    }
+
+    (annotated_game.to_annotated_pgn(&annotations), summaries)
+}
+
+fn analyze(
+    board: &Chess,
+    position_history: &[Zobrist64],
+    limit: &Limit,
+    transposition_table: &mut Arc<Mutex<Vec<Option<Node>>>>,
+) -> Analysis {
+    if board.is_game_over() {
+        return Analysis {
+            score: None,
+            best_move: None,
+            principal_variation: Vec::new(),
+            game_over_score: if board.is_checkmate() { -MATE } else { 0 },
+        };
+    }
+
+    let searching = Arc::new(AtomicBool::new(true));
+
+    let mut searcher = Searcher::new(Arc::clone(&searching), Arc::new(AtomicBool::new(false)));
+
+    match *limit {
+        Limit::MoveTime(move_time) => {
+            thread::spawn(move || timer::search_for_ms(move_time, searching));
+        }
+        Limit::Nodes(nodes) => searcher.max_nodes = Some(nodes),
+        Limit::Depth(depth) => searcher.max_depth = Some(depth),
+    }
+
+    let result = searcher.search(
+        board.clone(),
+        &mut position_history.to_vec(),
+        transposition_table,
+    );
+
+    Analysis {
+        score: Some(result.score),
+        best_move: result.best_move,
+        principal_variation: result.principal_variation,
+        game_over_score: 0,
+    }
+}
+
+// Winning chances in percent of a score, mates count as certain
+fn winning_chances(score: i16) -> f64 {
+    if score > MATE - MATE_MAX_PLIES {
+        100.0
+    } else if score < -MATE + MATE_MAX_PLIES {
+        0.0
+    } else {
+        100.0 / (1.0 + (-0.00368208 * score as f64).exp())
+    }
+}
+
+// Accuracy in percent of a move from its drop in winning chances
+fn move_accuracy(drop: f64) -> f64 {
+    (103.1668 * (-0.04354 * drop).exp() - 3.1669).clamp(0.0, 100.0)
+}
+
+// The evaluation after a move from white's side, given the score of the side to move next
+fn white_eval(mover: Color, score: i16) -> String {
+    format_eval(mover.fold_wb(-score, score))
+}
+
+fn format_eval(score: i16) -> String {
+    if score > MATE - MATE_MAX_PLIES {
+        format!("#{}", (MATE - score + 1) / 2)
+    } else if score < -MATE + MATE_MAX_PLIES {
+        format!("#-{}", (MATE + score + 1) / 2)
+    } else {
+        format!("{:.2}", score as f64 / 100.0)
+    }
+}
+
+// The first plies of a line in SAN with move numbers, from the position before it
+fn variation(board: &Chess, line: &[Move]) -> String {
+    let mut board = board.clone();
+    let mut tokens: Vec<String> = Vec::new();
+
+    for (i, m) in line.iter().take(VARIATION_PLIES).enumerate() {
+        if !board.is_legal(*m) {
+            break;
+        }
+
+        let fullmoves = board.fullmoves();
+
+        match board.turn() {
+            Color::White => tokens.push(format!("{fullmoves}.")),
+            Color::Black if i == 0 => tokens.push(format!("{fullmoves}...")),
+            Color::Black => {}
+        }
+
+        tokens.push(SanPlus::from_move_and_play_unchecked(&mut board, *m).to_string());
+    }
+
+    tokens.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mate_formatting() {
+        assert_eq!(format_eval(MATE - 1), "#1");
+        assert_eq!(format_eval(MATE - 3), "#2");
+        assert_eq!(format_eval(-MATE + 2), "#-1");
+        assert_eq!(format_eval(-MATE + 4), "#-2");
+
+        // White mates in one after its move, black to move is mated in two plies
+        assert_eq!(white_eval(Color::White, -MATE + 2), "#1");
+        // Black mates in one after its move
+        assert_eq!(white_eval(Color::Black, -MATE + 2), "#-1");
+        // White to move after black's move mates in two, or is mated in two
+        assert_eq!(white_eval(Color::Black, MATE - 3), "#2");
+        assert_eq!(white_eval(Color::Black, -MATE + 4), "#-2");
+        assert_eq!(white_eval(Color::White, MATE - 3), "#-2");
+    }
+
+    #[test]
+    fn eval_from_white() {
+        // Black to move after white's move, better for black
+        assert_eq!(white_eval(Color::White, 150), "-1.50");
+        assert_eq!(white_eval(Color::White, -25), "0.25");
+        // White to move after black's move
+        assert_eq!(white_eval(Color::Black, 150), "1.50");
+        assert_eq!(white_eval(Color::Black, 0), "0.00");
+    }
+
+    #[test]
+    fn judgement_thresholds() {
+        let judgement = |drop: f64| Judgement::from_drop(drop).map(Judgement::nag);
+
+        assert_eq!(judgement(0.0), None);
+        assert_eq!(judgement(9.9), None);
+        assert_eq!(judgement(10.0), Some("$6"));
+        assert_eq!(judgement(19.9), Some("$6"));
+        assert_eq!(judgement(20.0), Some("$2"));
+        assert_eq!(judgement(29.9), Some("$2"));
+        assert_eq!(judgement(30.0), Some("$4"));
+        assert_eq!(judgement(100.0), Some("$4"));
+
+        // Going from equal to three pawns down is a mistake, giving away a forced mate a blunder
+        let drop = winning_chances(0) - winning_chances(-300);
+
+        assert!(drop > MISTAKE && drop < BLUNDER);
+        assert_eq!(
+            Judgement::from_drop(winning_chances(MATE - 5) - winning_chances(0)),
+            Some(Judgement::Blunder)
+        );
+    }
+}
@@ -0,0 +1,885 @@
+use crate::correction::CorrectionHistory;
+use crate::epd::Epd;
+use crate::history::History;
+use crate::nnue::{AccumulatorStack, Network};
+use crate::params::{InternalIterative, SearchParams};
+use crate::pgn;
+use crate::random::Rng;
+use crate::search::{Node, Searcher, MATE};
+use crate::skill::Skill;
+use crate::sprt::{Decision, Pentanomial, Sprt};
+use crate::timer;
+use shakmaty::fen::Fen;
+use shakmaty::san::SanPlus;
+use shakmaty::uci::UciMove;
+use shakmaty::zobrist::Zobrist64;
+use shakmaty::{CastlingMode, Chess, Color, EnPassantMode, KnownOutcome, Move, Position};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const TRANSPOSITION_TABLE_SIZE_MB: usize = 16;
+const TRANSPOSITION_TABLE_LENGTH: usize =
+    TRANSPOSITION_TABLE_SIZE_MB * 1_000_000 / size_of::<Option<Node>>();
+
+const MAX_GAME_PLIES: usize = 600;
+
+// Resign when both engines agree on the score for this many moves each
+const RESIGN_SCORE: i16 = 1000;
+const RESIGN_MOVES: u32 = 3;
+
+// Draw when both engines see a balanced position for this many moves each
+const DRAW_SCORE: i16 = 10;
+const DRAW_MOVES: u32 = 8;
+const DRAW_MIN_PLIES: usize = 80;
+
+// Extra time an engine gets to answer before it is considered hung
+const RESPONSE_MARGIN_MS: u64 = 5000;
+
+#[derive(Clone, Copy)]
+enum Limit {
+    Clock { base: u64, increment: u64 },
+    MoveTime(u64),
+    Nodes(u64),
+}
+
+struct Options {
+    first: String,
+    second: String,
+    games: u64,
+    concurrency: usize,
+    openings: Vec<Chess>,
+    limit: Limit,
+    pgn: Option<String>,
+    sprt: Sprt,
+}
+
+/// Usage: match <engine> <engine> [games=N] [concurrency=N] [openings=<epd>]
+///        [tc=<seconds>+<increment>] [movetime=<ms>] [nodes=N] [pgn=<output>]
+///        [elo0=N] [elo1=N] [alpha=N] [beta=N]
+///
+/// An engine is the path of a UCI executable, or `self` for this engine played in-process,
+/// both optionally followed by options such as `self:evalfile=net.bin,AspirationWindow=30`.
+/// `self` takes `InternalIterative=None|Reduction|Deepening` to compare the two schemes.
+/// `self` also takes `skill=<level>` or `elo=<elo>` to play at a limited strength, and
+/// `nodes=N` to search at most N nodes per move whatever the match limit.
+pub fn run(args: &[String]) {
+    let options = match parse_options(args) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{error}");
+            eprintln!(
+                "usage: reggz match <engine> <engine> [games=N] [concurrency=N] [openings=<epd>] [tc=<s>+<inc>] [movetime=<ms>] [nodes=N] [pgn=<output>] [elo0=N] [elo1=N] [alpha=N] [beta=N]"
+            );
+            return;
+        }
+    };
+
+    let mut pgn_writer = match options.pgn.as_ref().map(File::create).transpose() {
+        Ok(file) => file.map(BufWriter::new),
+        Err(error) => {
+            eprintln!("failed to create pgn: {error}");
+            return;
+        }
+    };
+
+    let pairs = options.games.div_ceil(2);
+
+    let next_pair = AtomicU64::new(0);
+    let stop = AtomicBool::new(false);
+
+    let (sender, receiver) = mpsc::channel::<(u64, [GameRecord; 2])>();
+
+    thread::scope(|scope| {
+        for _ in 0..options.concurrency {
+            let sender = sender.clone();
+            let options = &options;
+            let next_pair = &next_pair;
+            let stop = &stop;
+
+            scope.spawn(move || {
+                let mut players =
+                    match (start_player(&options.first), start_player(&options.second)) {
+                        (Ok(first), Ok(second)) => [first, second],
+                        (Err(error), _) | (_, Err(error)) => {
+                            eprintln!("failed to start engine: {error}");
+                            return;
+                        }
+                    };
+
+                loop {
+                    let pair = next_pair.fetch_add(1, Ordering::Relaxed);
+
+                    if pair >= pairs || stop.load(Ordering::Relaxed) {
+                        return;
+                    }
+
+                    let opening = &options.openings[pair as usize % options.openings.len()];
+
+                    let [first, second] = &mut players;
+
+                    let games = [
+                        play_game(
+                            first.as_mut(),
+                            second.as_mut(),
+                            opening,
+                            options.limit,
+                            true,
+                        ),
+                        play_game(
+                            second.as_mut(),
+                            first.as_mut(),
+                            opening,
+                            options.limit,
+                            false,
+                        ),
+                    ];
+
+                    if sender.send((pair, games)).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+
+        drop(sender);
+
+        let mut pentanomial = Pentanomial::default();
+        let mut wins: u64 = 0;
+        let mut draws: u64 = 0;
+        let mut losses: u64 = 0;
+
+        let mut decided = false;
+
+        for (pair, games) in receiver {
+            for (game_index, record) in games.iter().enumerate() {
+                match record.first_points {
+                    2 => wins += 1,
+                    1 => draws += 1,
+                    _ => losses += 1,
+                }
+
+                if let Some(ref mut writer) = pgn_writer {
+                    let mut game = record.game.clone();
+
+                    game.headers.insert(
+                        1,
+                        (
+                            "Round".to_string(),
+                            format!("{}.{}", pair + 1, game_index + 1),
+                        ),
+                    );
+
+                    if let Err(error) = writer.write_all(game.to_pgn().as_bytes()) {
+                        eprintln!("failed to write pgn: {error}");
+                    }
+                }
+            }
+
+            if decided {
+                continue;
+            }
+
+            pentanomial.add(games[0].first_points, games[1].first_points);
+
+            let llr = pentanomial.llr(options.sprt.elo0, options.sprt.elo1);
+
+            let elo = pentanomial
+                .elo()
+                .map_or(String::from("-"), |(elo, margin)| {
+                    format!("{elo:.1} +- {margin:.1}")
+                });
+
+            println!(
+                "games {} W {wins} D {draws} L {losses} elo {elo} llr {llr:.2} ({:.2}, {:.2}) [{}]",
+                wins + draws + losses,
+                options.sprt.lower_bound,
+                options.sprt.upper_bound,
+                pentanomial
+                    .counts
+                    .iter()
+                    .map(u64::to_string)
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            );
+
+            if let Some(decision) = options.sprt.decision(llr) {
+                match decision {
+                    Decision::AcceptH1 => {
+                        println!("sprt accepted H1 (elo >= {})", options.sprt.elo1)
+                    }
+                    Decision::AcceptH0 => {
+                        println!("sprt accepted H0 (elo <= {})", options.sprt.elo0)
+                    }
+                }
+
+                stop.store(true, Ordering::Relaxed);
+
+                decided = true;
+            }
+        }
+    });
+
+    if let Some(ref mut writer) = pgn_writer {
+        if let Err(error) = writer.flush() {
+            eprintln!("failed to write pgn: {error}");
+        }
+    }
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let (Some(first), Some(second)) = (args.first(), args.get(1)) else {
+        return Err(String::from("two engines are required"));
+    };
+
+    let mut options = Options {
+        first: first.clone(),
+        second: second.clone(),
+        games: 200,
+        concurrency: 1,
+        openings: vec![Chess::new()],
+        limit: Limit::Clock {
+            base: 10_000,
+            increment: 100,
+        },
+        pgn: None,
+        sprt: Sprt::new(0.0, 5.0, 0.05, 0.05),
+    };
+
+    let (mut elo0, mut elo1, mut alpha, mut beta) = (0.0, 5.0, 0.05, 0.05);
+
+    for arg in &args[2..] {
+        let Some((key, value)) = arg.split_once('=') else {
+            return Err(format!("expected key=value, got {arg}"));
+        };
+
+        let invalid = || format!("invalid value {value} for {key}");
+
+        match key {
+            "games" => options.games = value.parse().map_err(|_| invalid())?,
+            "concurrency" => options.concurrency = value.parse().map_err(|_| invalid())?,
+            "openings" => options.openings = load_openings(value)?,
+            "tc" => {
+                let (base, increment) = value.split_once('+').unwrap_or((value, "0"));
+
+                let base: f64 = base.parse().map_err(|_| invalid())?;
+                let increment: f64 = increment.parse().map_err(|_| invalid())?;
+
+                options.limit = Limit::Clock {
+                    base: (base * 1000.0) as u64,
+                    increment: (increment * 1000.0) as u64,
+                };
+            }
+            "movetime" => options.limit = Limit::MoveTime(value.parse().map_err(|_| invalid())?),
+            "nodes" => options.limit = Limit::Nodes(value.parse().map_err(|_| invalid())?),
+            "pgn" => options.pgn = Some(value.to_string()),
+            "elo0" => elo0 = value.parse().map_err(|_| invalid())?,
+            "elo1" => elo1 = value.parse().map_err(|_| invalid())?,
+            "alpha" => alpha = value.parse().map_err(|_| invalid())?,
+            "beta" => beta = value.parse().map_err(|_| invalid())?,
+            _ => return Err(format!("unknown option {key}")),
+        }
+    }
+
+    options.sprt = Sprt::new(elo0, elo1, alpha, beta);
+    options.concurrency = options.concurrency.max(1);
+
+    Ok(options)
+}
+
+fn load_openings(path: &str) -> Result<Vec<Chess>, String> {
+    let text = fs::read_to_string(path).map_err(|error| format!("{path}: {error}"))?;
+
+    let openings: Vec<Chess> = text
+        .lines()
+        .filter_map(Epd::parse)
+        .map(|epd| epd.board)
+        .collect();
+
+    if openings.is_empty() {
+        return Err(format!("{path}: no positions"));
+    }
+
+    Ok(openings)
+}
+
+#[derive(Clone)]
+struct GameRecord {
+    game: pgn::Game,
+    // Half points of the first engine
+    first_points: u8,
+}
+
+/// A game in progress as seen by the player to move
+struct GameState<'a> {
+    opening: &'a Chess,
+    moves: &'a [Move],
+    board: &'a Chess,
+    position_history: &'a [Zobrist64],
+    // Remaining time of black and white, indexed by color
+    clock: [u64; 2],
+    limit: Limit,
+}
+
+trait Player {
+    fn name(&self) -> String;
+
+    fn new_game(&mut self) -> Result<(), String>;
+
+    /// The move to play and the score from the player's perspective, if it reports one
+    fn play(&mut self, state: &GameState) -> Result<(Move, Option<i16>), String>;
+}
+
+fn start_player(spec: &str) -> Result<Box<dyn Player>, String> {
+    let (path, options) = match spec.split_once(':') {
+        Some((path, options)) => (path, options),
+        None => (spec, ""),
+    };
+
+    let options: Vec<(&str, &str)> = options
+        .split(',')
+        .filter(|option| !option.is_empty())
+        .map(|option| option.split_once('=').unwrap_or((option, "")))
+        .collect();
+
+    if path == "self" {
+        Ok(Box::new(InternalPlayer::new(&options)?))
+    } else {
+        Ok(Box::new(UciPlayer::start(path, &options)?))
+    }
+}
+
+fn play_game(
+    white: &mut dyn Player,
+    black: &mut dyn Player,
+    opening: &Chess,
+    limit: Limit,
+    first_is_white: bool,
+) -> GameRecord {
+    let mut board = opening.clone();
+    let mut moves: Vec<Move> = Vec::new();
+    let mut sans: Vec<String> = Vec::new();
+    let mut position_history: Vec<Zobrist64> = vec![board.zobrist_hash(EnPassantMode::Legal)];
+
+    let mut clock: [u64; 2] = match limit {
+        Limit::Clock { base, .. } => [base, base],
+        _ => [0, 0],
+    };
+
+    // Last score reported by each side, and how long the scores have agreed
+    let mut last_score: [Option<i16>; 2] = [None, None];
+    let mut resign_plies: u32 = 0;
+    let mut draw_plies: u32 = 0;
+
+    let mut outcome: Option<(KnownOutcome, String)> = None;
+
+    if let Err(error) = white.new_game().and_then(|_| black.new_game()) {
+        outcome = Some((KnownOutcome::Draw, format!("abandoned: {error}")));
+    }
+
+    while outcome.is_none() {
+        let hash = *position_history.last().unwrap();
+
+        if let Some(result) = board.outcome().known() {
+            outcome = Some((result, String::from("normal")));
+            break;
+        } else if board.halfmoves() >= 100 {
+            outcome = Some((KnownOutcome::Draw, String::from("fifty-move rule")));
+            break;
+        } else if position_history.iter().filter(|h| **h == hash).count() >= 3 {
+            outcome = Some((KnownOutcome::Draw, String::from("threefold repetition")));
+            break;
+        } else if moves.len() >= MAX_GAME_PLIES {
+            outcome = Some((
+                KnownOutcome::Draw,
+                String::from("adjudication: game length"),
+            ));
+            break;
+        }
+
+        let turn = board.turn();
+        let side = turn as usize;
+        let loss = KnownOutcome::Decisive { winner: !turn };
+
+        let state = GameState {
+            opening,
+            moves: &moves,
+            board: &board,
+            position_history: &position_history,
+            clock,
+            limit,
+        };
+
+        let start_time = Instant::now();
+
+        let played = match turn {
+            Color::White => white.play(&state),
+            Color::Black => black.play(&state),
+        };
+
+        let elapsed = start_time.elapsed().as_millis() as u64;
+
+        let (played_move, score) = match played {
+            Ok(played) => played,
+            Err(error) => {
+                outcome = Some((loss, error));
+                break;
+            }
+        };
+
+        if let Limit::Clock { increment, .. } = limit {
+            if elapsed > clock[side] {
+                outcome = Some((loss, String::from("time forfeit")));
+                break;
+            }
+
+            clock[side] = clock[side] - elapsed + increment;
+        }
+
+        sans.push(SanPlus::from_move_and_play_unchecked(&mut board, played_move).to_string());
+        moves.push(played_move);
+        position_history.push(board.zobrist_hash(EnPassantMode::Legal));
+
+        // Adjudication, both engines have to agree
+        if let (Some(score), Some(opponent_score)) = (score, last_score[1 - side]) {
+            if (score >= RESIGN_SCORE && opponent_score <= -RESIGN_SCORE)
+                || (score <= -RESIGN_SCORE && opponent_score >= RESIGN_SCORE)
+            {
+                resign_plies += 1;
+            } else {
+                resign_plies = 0;
+            }
+
+            if moves.len() >= DRAW_MIN_PLIES
+                && score.abs() <= DRAW_SCORE
+                && opponent_score.abs() <= DRAW_SCORE
+            {
+                draw_plies += 1;
+            } else {
+                draw_plies = 0;
+            }
+
+            if resign_plies >= 2 * RESIGN_MOVES {
+                let winner = if score > 0 { turn } else { !turn };
+
+                outcome = Some((
+                    KnownOutcome::Decisive { winner },
+                    String::from("adjudication: resign"),
+                ));
+            } else if draw_plies >= 2 * DRAW_MOVES {
+                outcome = Some((KnownOutcome::Draw, String::from("adjudication: draw")));
+            }
+        }
+
+        last_score[side] = score;
+    }
+
+    let (result, termination) = outcome.unwrap_or((KnownOutcome::Draw, String::new()));
+
+    let white_points: u8 = match result {
+        KnownOutcome::Decisive {
+            winner: Color::White,
+        } => 2,
+        KnownOutcome::Draw => 1,
+        KnownOutcome::Decisive {
+            winner: Color::Black,
+        } => 0,
+    };
+
+    let mut headers: Vec<(String, String)> = vec![
+        (String::from("Event"), String::from("reggz match")),
+        (String::from("White"), white.name()),
+        (String::from("Black"), black.name()),
+        (String::from("Result"), result.to_string()),
+        (String::from("Termination"), termination),
+    ];
+
+    if *opening != Chess::new() {
+        headers.push((String::from("SetUp"), String::from("1")));
+        headers.push((
+            String::from("FEN"),
+            Fen::from_position(opening, EnPassantMode::Legal).to_string(),
+        ));
+    }
+
+    GameRecord {
+        game: pgn::Game {
+            headers,
+            moves: sans,
+            result: result.to_string(),
+        },
+        first_points: if first_is_white {
+            white_points
+        } else {
+            2 - white_points
+        },
+    }
+}
+
+/// This engine searching in-process with its own transposition table and histories
+struct InternalPlayer {
+    name: String,
+    network: Option<Arc<Network>>,
+    params: SearchParams,
+    skill: Option<Skill>,
+    // Nodes per move on top of the match limit, for reference opponents of a fixed strength
+    nodes: Option<u64>,
+    rng: Rng,
+    transposition_table: Arc<Mutex<Vec<Option<Node>>>>,
+    history: History,
+    correction: CorrectionHistory,
+}
+
+impl InternalPlayer {
+    fn new(options: &[(&str, &str)]) -> Result<InternalPlayer, String> {
+        let mut network = Network::embedded().map(Arc::new);
+
+        let mut evalfile: Option<Arc<Network>> = None;
+        let mut use_nnue = network.is_some();
+        let mut params = SearchParams::default();
+        let mut skill: Option<Skill> = None;
+        let mut nodes: Option<u64> = None;
+
+        for (name, value) in options {
+            match name.to_lowercase().as_str() {
+                "evalfile" => evalfile = Some(Arc::new(Network::load(value)?)),
+                "nnue" => use_nnue = *value == "true",
+                "skill" => {
+                    skill = Skill::from_level(
+                        value
+                            .parse()
+                            .map_err(|_| format!("invalid value {value} for {name}"))?,
+                    )
+                }
+                "elo" => {
+                    skill = Skill::from_elo(
+                        value
+                            .parse()
+                            .map_err(|_| format!("invalid value {value} for {name}"))?,
+                    )
+                }
+                "nodes" => {
+                    nodes = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("invalid value {value} for {name}"))?,
+                    )
+                }
+                "internaliterative" => {
+                    params.internal_iterative = InternalIterative::from_name(value)?
+                }
+                _ => params.set(
+                    name,
+                    value
+                        .parse()
+                        .map_err(|_| format!("invalid value {value} for {name}"))?,
+                )?,
+            }
+        }
+
+        if evalfile.is_some() {
+            network = evalfile;
+            use_nnue = true;
+        }
+
+        let name = if options.is_empty() {
+            String::from("reggz")
+        } else {
+            format!(
+                "reggz {}",
+                options
+                    .iter()
+                    .map(|(name, value)| format!("{name}={value}"))
+                    .collect::<Vec<String>>()
+                    .join(",")
+            )
+        };
+
+        Ok(InternalPlayer {
+            name,
+            network: network.filter(|_| use_nnue),
+            params,
+            skill,
+            nodes,
+            rng: Rng::from_time(0),
+            transposition_table: Arc::new(Mutex::new(vec![None; TRANSPOSITION_TABLE_LENGTH])),
+            history: History::new(),
+            correction: CorrectionHistory::new(),
+        })
+    }
+}
+
+impl Player for InternalPlayer {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn new_game(&mut self) -> Result<(), String> {
+        for node in self.transposition_table.lock().unwrap().iter_mut() {
+            *node = None;
+        }
+
+        self.history = History::new();
+        self.correction = CorrectionHistory::new();
+
+        Ok(())
+    }
+
+    fn play(&mut self, state: &GameState) -> Result<(Move, Option<i16>), String> {
+        let searching = Arc::new(AtomicBool::new(true));
+
+        let mut searcher = Searcher::new(Arc::clone(&searching), Arc::new(AtomicBool::new(false)));
+
+        searcher.params = self.params.clone();
+        searcher.contempt = Some(0);
+        searcher.nnue = self
+            .network
+            .as_ref()
+            .map(|network| AccumulatorStack::new(Arc::clone(network)));
+
+        let move_time = match state.limit {
+            Limit::Clock { increment, .. } => Some(timer::allocate(
+                state.clock[state.board.turn() as usize],
+                increment,
+            )),
+            Limit::MoveTime(move_time) => Some(move_time),
+            Limit::Nodes(nodes) => {
+                searcher.max_nodes = Some(nodes);
+                None
+            }
+        };
+
+        if let Some(nodes) = self.nodes {
+            searcher.max_nodes = Some(searcher.max_nodes.map_or(nodes, |n| n.min(nodes)));
+        }
+
+        if let Some(skill) = self.skill {
+            skill.limit(&mut searcher);
+        }
+
+        if let Some(move_time) = move_time {
+            thread::spawn(move || timer::search_for_ms(move_time, searching));
+        }
+
+        let mut position_history = state.position_history.to_vec();
+
+        std::mem::swap(&mut searcher.history, &mut self.history);
+        std::mem::swap(&mut searcher.correction, &mut self.correction);
+
+        let result = searcher.search(
+            state.board.clone(),
+            &mut position_history,
+            &mut self.transposition_table,
+        );
+
+        std::mem::swap(&mut searcher.history, &mut self.history);
+        std::mem::swap(&mut searcher.correction, &mut self.correction);
+
+        let best_move = match self.skill {
+            Some(skill) => skill.pick(&result.lines, &mut self.rng),
+            None => result.best_move,
+        };
+
+        // A search stopped before its first iteration still plays a move
+        let best_move = best_move
+            .or(result.best_move)
+            .or_else(|| state.board.legal_moves().first().copied())
+            .ok_or_else(|| String::from("no move"))?;
+
+        Ok((best_move, Some(result.score)))
+    }
+}
+
+/// An external engine speaking UCI over its standard input and output
+struct UciPlayer {
+    name: String,
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+}
+
+impl UciPlayer {
+    fn start(path: &str, options: &[(&str, &str)]) -> Result<UciPlayer, String> {
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|error| format!("{path}: {error}"))?;
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        let (sender, lines) = mpsc::channel::<String>();
+
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    return;
+                }
+            }
+        });
+
+        let mut player = UciPlayer {
+            name: path.to_string(),
+            child,
+            stdin,
+            lines,
+        };
+
+        player.send("uci")?;
+
+        let timeout = Duration::from_millis(RESPONSE_MARGIN_MS);
+
+        loop {
+            let line = player.read_line(timeout)?;
+
+            if let Some(name) = line.strip_prefix("id name ") {
+                player.name = name.trim().to_string();
+            } else if line.trim() == "uciok" {
+                break;
+            }
+        }
+
+        for (name, value) in options {
+            player.send(&format!("setoption name {name} value {value}"))?;
+        }
+
+        player.wait_ready()?;
+
+        Ok(player)
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), String> {
+        writeln!(self.stdin, "{command}")
+            .and_then(|_| self.stdin.flush())
+            .map_err(|error| format!("{}: {error}", self.name))
+    }
+
+    fn read_line(&mut self, timeout: Duration) -> Result<String, String> {
+        self.lines
+            .recv_timeout(timeout)
+            .map_err(|_| format!("{} stopped responding", self.name))
+    }
+
+    fn wait_ready(&mut self) -> Result<(), String> {
+        self.send("isready")?;
+
+        while self
+            .read_line(Duration::from_millis(RESPONSE_MARGIN_MS))?
+            .trim()
+            != "readyok"
+        {}
+
+        Ok(())
+    }
+}
+
+impl Player for UciPlayer {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn new_game(&mut self) -> Result<(), String> {
+        self.send("ucinewgame")?;
+        self.wait_ready()
+    }
+
+    fn play(&mut self, state: &GameState) -> Result<(Move, Option<i16>), String> {
+        let moves: Vec<String> = state
+            .moves
+            .iter()
+            .map(|m| m.to_uci(CastlingMode::Standard).to_string())
+            .collect();
+
+        let mut position = format!(
+            "position fen {}",
+            Fen::from_position(state.opening, EnPassantMode::Legal)
+        );
+
+        if !moves.is_empty() {
+            position.push_str(" moves ");
+            position.push_str(&moves.join(" "));
+        }
+
+        self.send(&position)?;
+
+        let (go, timeout) = match state.limit {
+            Limit::Clock { increment, .. } => (
+                format!(
+                    "go wtime {} btime {} winc {increment} binc {increment}",
+                    state.clock[Color::White as usize],
+                    state.clock[Color::Black as usize]
+                ),
+                state.clock[state.board.turn() as usize],
+            ),
+            Limit::MoveTime(move_time) => (format!("go movetime {move_time}"), move_time),
+            // Node limited searches are usually quick, give them a minute
+            Limit::Nodes(nodes) => (format!("go nodes {nodes}"), 60_000),
+        };
+
+        self.send(&go)?;
+
+        let timeout = Duration::from_millis(timeout + RESPONSE_MARGIN_MS);
+
+        let mut score: Option<i16> = None;
+
+        loop {
+            let line = self.read_line(timeout)?;
+
+            let mut tokens = line.split_whitespace();
+
+            match tokens.next() {
+                Some("info") => {
+                    while let Some(token) = tokens.next() {
+                        if token != "score" {
+                            continue;
+                        }
+
+                        let value = tokens.nth(1).and_then(|v| v.parse::<i32>().ok());
+
+                        score = match (line.contains("score mate"), value) {
+                            (true, Some(mate)) if mate > 0 => Some(MATE - (2 * mate - 1) as i16),
+                            (true, Some(mate)) => Some(-MATE - (2 * mate) as i16),
+                            (false, Some(cp)) => Some(cp.clamp(-MATE as i32, MATE as i32) as i16),
+                            _ => score,
+                        };
+                    }
+                }
+                Some("bestmove") => {
+                    let uci_move = tokens.next().unwrap_or("0000");
+
+                    let legal_move = UciMove::from_ascii(uci_move.as_bytes())
+                        .ok()
+                        .and_then(|m| m.to_move(state.board).ok())
+                        .ok_or_else(|| format!("illegal move {uci_move}"))?;
+
+                    return Ok((legal_move, score));
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Drop for UciPlayer {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+
+        for _ in 0..100 {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
@@ -0,0 +1,106 @@
+use shakmaty::zobrist::{Zobrist64, ZobristValue};
+use shakmaty::{Board, Color, Piece, Role, Square};
+
+const SIZE: usize = 16384;
+// Entries are stored in sixteenths of a centipawn, up to this limit
+const LIMIT: i32 = 16384;
+const GRAIN: i32 = 16;
+
+/// Corrections to the static evaluation learned from search results, keyed by the pawn
+/// structure, the material and each side's non-pawn pieces
+pub struct CorrectionHistory {
+    // [turn][pawn key]
+    pawn: Vec<i16>,
+    // [turn][material key]
+    material: Vec<i16>,
+    // [color][turn][non-pawn key of color]
+    non_pawn: Vec<i16>,
+}
+
+impl CorrectionHistory {
+    pub fn new() -> CorrectionHistory {
+        CorrectionHistory {
+            pawn: vec![0; 2 * SIZE],
+            material: vec![0; 2 * SIZE],
+            non_pawn: vec![0; 2 * 2 * SIZE],
+        }
+    }
+
+    /// The static evaluation adjusted by the corrections of the position's keys
+    pub fn correct(&self, board: &Board, turn: Color, evaluation: i16) -> i16 {
+        let indices = Indices::new(board, turn);
+
+        let correction = 2 * self.pawn[indices.pawn] as i32
+            + self.material[indices.material] as i32
+            + self.non_pawn[indices.non_pawn[0]] as i32
+            + self.non_pawn[indices.non_pawn[1]] as i32;
+
+        (evaluation as i32 + correction / (4 * GRAIN)).clamp(-10000, 10000) as i16
+    }
+
+    /// Moves the corrections towards the difference between the search result and the
+    /// static evaluation, more so for deeper searches
+    pub fn update(&mut self, board: &Board, turn: Color, depth: i16, difference: i32) {
+        let indices = Indices::new(board, turn);
+
+        let bonus = (difference * GRAIN * depth as i32 / 8).clamp(-LIMIT / 4, LIMIT / 4);
+
+        update(&mut self.pawn[indices.pawn], bonus);
+        update(&mut self.material[indices.material], bonus);
+
+        for index in indices.non_pawn {
+            update(&mut self.non_pawn[index], bonus);
+        }
+    }
+}
+
+struct Indices {
+    pawn: usize,
+    material: usize,
+    non_pawn: [usize; 2],
+}
+
+impl Indices {
+    fn new(board: &Board, turn: Color) -> Indices {
+        let mut pawn_key = 0;
+        let mut material_key = 0;
+        let mut non_pawn_keys = [0; 2];
+
+        for (square, piece) in board {
+            let key = Zobrist64::zobrist_for_piece(square, piece).0;
+
+            if piece.role == Role::Pawn {
+                pawn_key ^= key;
+            } else {
+                non_pawn_keys[usize::from(piece.color.is_white())] ^= key;
+            }
+        }
+
+        // The material key hashes each piece count, using the squares as counters
+        for color in Color::ALL {
+            for role in Role::ALL {
+                let piece = Piece { color, role };
+
+                for count in 0..board.by_piece(piece).count() {
+                    material_key ^=
+                        Zobrist64::zobrist_for_piece(Square::new(count as u32), piece).0;
+                }
+            }
+        }
+
+        let turn = usize::from(turn.is_white());
+
+        Indices {
+            pawn: turn * SIZE + pawn_key as usize % SIZE,
+            material: turn * SIZE + material_key as usize % SIZE,
+            non_pawn: [0, 1]
+                .map(|color| (color * 2 + turn) * SIZE + non_pawn_keys[color] as usize % SIZE),
+        }
+    }
+}
+
+fn update(entry: &mut i16, bonus: i32) {
+    let value = *entry as i32;
+
+    *entry = (value + bonus - value * bonus.abs() / LIMIT) as i16;
+}
@@ -0,0 +1,371 @@
+use crate::random::Rng;
+use crate::search::{Node, Searcher, MATE, MATE_MAX_PLIES};
+use shakmaty::fen::Fen;
+use shakmaty::zobrist::Zobrist64;
+use shakmaty::{
+    Bitboard, Board, CastlingMode, Chess, Color, EnPassantMode, FromSetup, Piece, Position, Role,
+    Setup, Square,
+};
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::num::NonZeroU32;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::SystemTime;
+
+const RECORD_SIZE: usize = 32;
+
+const RANDOM_PLIES: usize = 8;
+const MAX_OPENING_SCORE: i16 = 1000;
+const MAX_GAME_PLIES: usize = 600;
+const TRANSPOSITION_TABLE_SIZE_MB: usize = 16;
+const TRANSPOSITION_TABLE_LENGTH: usize =
+    TRANSPOSITION_TABLE_SIZE_MB * 1_000_000 / size_of::<Option<Node>>();
+
+/// A position with its search score from white's perspective and the game result
+/// (0 = black win, 1 = draw, 2 = white win)
+pub struct Record {
+    pub board: Chess,
+    pub score: i16,
+    pub result: u8,
+}
+
+impl Record {
+    /// Packs the record into 32 bytes: occupancy, piece nibbles, score, result,
+    /// side to move and castling flags, en passant square, halfmove and fullmove clocks
+    pub fn encode(&self) -> [u8; RECORD_SIZE] {
+        let mut bytes = [0u8; RECORD_SIZE];
+
+        let board = self.board.board();
+
+        bytes[0..8].copy_from_slice(&board.occupied().0.to_le_bytes());
+
+        for (i, (_, piece)) in board.iter().enumerate() {
+            let nibble = piece.role as u8 | if piece.color == Color::Black { 8 } else { 0 };
+
+            bytes[8 + i / 2] |= nibble << (4 * (i % 2));
+        }
+
+        bytes[24..26].copy_from_slice(&self.score.to_le_bytes());
+        bytes[26] = self.result;
+
+        let castling_rights = self.board.castles().castling_rights();
+
+        bytes[27] = (self.board.turn() == Color::White) as u8
+            | (castling_rights.contains(Square::A1) as u8) << 1
+            | (castling_rights.contains(Square::H1) as u8) << 2
+            | (castling_rights.contains(Square::A8) as u8) << 3
+            | (castling_rights.contains(Square::H8) as u8) << 4;
+
+        bytes[28] = self
+            .board
+            .ep_square(EnPassantMode::Legal)
+            .map_or(64, u8::from);
+        bytes[29] = self.board.halfmoves().min(255) as u8;
+        bytes[30..32]
+            .copy_from_slice(&(self.board.fullmoves().get().min(65535) as u16).to_le_bytes());
+
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8; RECORD_SIZE]) -> Option<Record> {
+        let occupied = Bitboard(u64::from_le_bytes(bytes[0..8].try_into().ok()?));
+
+        let mut board = Board::empty();
+
+        for (i, square) in occupied.into_iter().enumerate() {
+            let nibble = (bytes[8 + i / 2] >> (4 * (i % 2))) & 0xf;
+
+            let role = Role::try_from(nibble & 7).ok()?;
+            let color = if nibble & 8 != 0 {
+                Color::Black
+            } else {
+                Color::White
+            };
+
+            board.set_piece_at(square, Piece { color, role });
+        }
+
+        let flags = bytes[27];
+
+        let castling_rights = [Square::A1, Square::H1, Square::A8, Square::H8]
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| flags & (2 << i) != 0)
+            .fold(Bitboard::EMPTY, |rights, (_, square)| rights.with(square));
+
+        let mut setup = Setup::empty();
+
+        setup.board = board;
+        setup.turn = Color::from_white(flags & 1 != 0);
+        setup.castling_rights = castling_rights;
+        setup.ep_square = Square::try_from(bytes[28]).ok();
+        setup.halfmoves = bytes[29] as u32;
+        setup.fullmoves = NonZeroU32::new(u16::from_le_bytes([bytes[30], bytes[31]]) as u32)?;
+
+        Some(Record {
+            board: Chess::from_setup(setup, CastlingMode::Standard).ok()?,
+            score: i16::from_le_bytes([bytes[24], bytes[25]]),
+            result: bytes[26],
+        })
+    }
+
+    /// EPD line with the score from the side to move's perspective and the game result
+    pub fn to_epd(&self) -> String {
+        let fen = Fen::from_position(&self.board, EnPassantMode::Legal).to_string();
+        let epd: Vec<&str> = fen.split_whitespace().take(4).collect();
+
+        let score = match self.board.turn() {
+            Color::White => self.score,
+            Color::Black => -self.score,
+        };
+
+        let result = match self.result {
+            0 => "0-1",
+            2 => "1-0",
+            _ => "1/2-1/2",
+        };
+
+        format!("{} ce {score}; c9 \"{result}\";", epd.join(" "))
+    }
+}
+
+struct Options {
+    games: u64,
+    threads: usize,
+    nodes: u64,
+}
+
+/// Usage: datagen <output> [games] [threads] [nodes]
+///        datagen export <input> <output>
+pub fn run(args: &[String]) {
+    if let Some("export") = args.first().map(String::as_str) {
+        match (args.get(1), args.get(2)) {
+            (Some(input), Some(output)) => {
+                if let Err(error) = export(input, output) {
+                    eprintln!("export failed: {error}");
+                }
+            }
+            _ => eprintln!("usage: reggz datagen export <input> <output>"),
+        }
+
+        return;
+    }
+
+    let Some(output_path) = args.first() else {
+        eprintln!("usage: reggz datagen <output> [games] [threads] [nodes]");
+        return;
+    };
+
+    let options = Options {
+        games: args.get(1).and_then(|v| v.parse().ok()).unwrap_or(1000),
+        threads: args.get(2).and_then(|v| v.parse().ok()).unwrap_or(1),
+        nodes: args.get(3).and_then(|v| v.parse().ok()).unwrap_or(5000),
+    };
+
+    let mut writer = match File::create(output_path) {
+        Ok(file) => BufWriter::new(file),
+        Err(error) => {
+            eprintln!("failed to create {output_path}: {error}");
+            return;
+        }
+    };
+
+    let games_started = AtomicU64::new(0);
+    let start_time = SystemTime::now();
+
+    let (sender, receiver) = mpsc::channel::<Vec<Record>>();
+
+    thread::scope(|scope| {
+        for thread_index in 0..options.threads {
+            let sender = sender.clone();
+            let options = &options;
+            let games_started = &games_started;
+
+            scope.spawn(move || {
+                let mut rng = Rng::from_time(thread_index as u64);
+
+                let mut transposition_table =
+                    Arc::new(Mutex::new(vec![None; TRANSPOSITION_TABLE_LENGTH]));
+
+                while games_started.fetch_add(1, Ordering::Relaxed) < options.games {
+                    let records = play_game(&mut rng, options.nodes, &mut transposition_table);
+
+                    if sender.send(records).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+
+        drop(sender);
+
+        let mut games: u64 = 0;
+        let mut positions: u64 = 0;
+
+        for records in receiver {
+            for record in &records {
+                if let Err(error) = writer.write_all(&record.encode()) {
+                    eprintln!("failed to write {output_path}: {error}");
+                    return;
+                }
+            }
+
+            games += 1;
+            positions += records.len() as u64;
+
+            if games.is_multiple_of(10) || games == options.games {
+                let seconds = start_time.elapsed().map_or(0, |e| e.as_secs()).max(1);
+
+                eprintln!(
+                    "games {games} positions {positions} positions/s {}",
+                    positions / seconds
+                );
+            }
+        }
+    });
+
+    if let Err(error) = writer.flush() {
+        eprintln!("failed to write {output_path}: {error}");
+    }
+}
+
+fn export(input: &str, output: &str) -> std::io::Result<()> {
+    let mut bytes = Vec::new();
+
+    File::open(input)?.read_to_end(&mut bytes)?;
+
+    let mut writer = BufWriter::new(File::create(output)?);
+
+    for chunk in bytes.chunks_exact(RECORD_SIZE) {
+        if let Some(record) = Record::decode(chunk.try_into().unwrap()) {
+            writeln!(writer, "{}", record.to_epd())?;
+        }
+    }
+
+    writer.flush()
+}
+
+fn search(
+    board: &Chess,
+    position_history: &mut Vec<Zobrist64>,
+    nodes: u64,
+    transposition_table: &mut Arc<Mutex<Vec<Option<Node>>>>,
+) -> (Option<shakmaty::Move>, i16) {
+    let mut searcher = Searcher::new(
+        Arc::new(AtomicBool::new(true)),
+        Arc::new(AtomicBool::new(false)),
+    );
+
+    searcher.max_nodes = Some(nodes);
+    searcher.contempt = Some(0);
+
+    let result = searcher.search(board.clone(), position_history, transposition_table);
+
+    (result.best_move, result.score)
+}
+
+fn random_opening(
+    rng: &mut Rng,
+    nodes: u64,
+    transposition_table: &mut Arc<Mutex<Vec<Option<Node>>>>,
+) -> Chess {
+    loop {
+        let mut board = Chess::new();
+
+        for _ in 0..RANDOM_PLIES {
+            let legal_moves = board.legal_moves();
+
+            if legal_moves.is_empty() {
+                break;
+            }
+
+            board.play_unchecked(legal_moves[rng.below(legal_moves.len() as u64) as usize]);
+        }
+
+        if board.is_game_over() {
+            continue;
+        }
+
+        let mut position_history = vec![board.zobrist_hash(EnPassantMode::Legal)];
+
+        let (_, score) = search(&board, &mut position_history, nodes, transposition_table);
+
+        if score.abs() <= MAX_OPENING_SCORE {
+            return board;
+        }
+    }
+}
+
+fn play_game(
+    rng: &mut Rng,
+    nodes: u64,
+    transposition_table: &mut Arc<Mutex<Vec<Option<Node>>>>,
+) -> Vec<Record> {
+    for node in transposition_table.lock().unwrap().iter_mut() {
+        *node = None;
+    }
+
+    let mut board = random_opening(rng, nodes, transposition_table);
+
+    let mut position_history: Vec<Zobrist64> = vec![board.zobrist_hash(EnPassantMode::Legal)];
+
+    let mut records: Vec<Record> = Vec::new();
+
+    let mut result: u8 = 1;
+
+    for _ in 0..MAX_GAME_PLIES {
+        if let Some(outcome) = board.outcome().known() {
+            result = match outcome.winner() {
+                Some(Color::White) => 2,
+                Some(Color::Black) => 0,
+                None => 1,
+            };
+
+            break;
+        }
+
+        let hash = *position_history.last().unwrap();
+
+        if board.halfmoves() >= 100 || position_history.iter().filter(|h| **h == hash).count() >= 3
+        {
+            break;
+        }
+
+        let (best_move, score) = search(&board, &mut position_history, nodes, transposition_table);
+
+        let Some(best_move) = best_move else {
+            break;
+        };
+
+        let white_score = match board.turn() {
+            Color::White => score,
+            Color::Black => -score,
+        };
+
+        let is_mate_score = score.abs() > MATE - MATE_MAX_PLIES;
+
+        if !board.is_check()
+            && !best_move.is_capture()
+            && !best_move.is_promotion()
+            && !is_mate_score
+        {
+            records.push(Record {
+                board: board.clone(),
+                score: white_score,
+                result: 0,
+            });
+        }
+
+        board.play_unchecked(best_move);
+
+        position_history.push(board.zobrist_hash(EnPassantMode::Legal));
+    }
+
+    for record in &mut records {
+        record.result = result;
+    }
+
+    records
+}
@@ -0,0 +1,478 @@
+use crate::evaluate::material_value;
+use shakmaty::attacks::{king_attacks, pawn_attacks};
+use shakmaty::{Bitboard, Board, ByRole, Color, File, Rank, Role, Square};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+// Scores of won endings, far above any material balance but below mates and tablebase wins
+const KNOWN_WIN: i16 = 5000;
+
+// Evaluates an ending from the strong side's perspective, given the side to move
+type Evaluator = fn(&Board, Color, Color) -> i16;
+
+// Endings with their own evaluation, by the material of the strong and the weak side
+static ENDGAMES: [(&str, &str, Evaluator); 5] = [
+    ("KBN", "K", kbnk),
+    ("KNN", "K", |_, _, _| 0),
+    ("KP", "K", kpk),
+    ("KR", "KP", krkp),
+    ("KQ", "KP", kqkp),
+];
+
+/// Evaluation of an ending with known rules from white's perspective, if the material
+/// matches one
+pub fn evaluate(board: &Board, turn: Color) -> Option<i16> {
+    let white = signature(board, Color::White);
+    let black = signature(board, Color::Black);
+
+    let (strong, score) = if let Some(evaluator) = endgames().get(&(white, black)) {
+        (Color::White, evaluator(board, Color::White, turn))
+    } else if let Some(evaluator) = endgames().get(&(black, white)) {
+        (Color::Black, evaluator(board, Color::Black, turn))
+    } else if black == 0 && non_pawn_material(board, Color::White) >= material_value(Role::Rook) {
+        (Color::White, kxk(board, Color::White, turn))
+    } else if white == 0 && non_pawn_material(board, Color::Black) >= material_value(Role::Rook) {
+        (Color::Black, kxk(board, Color::Black, turn))
+    } else {
+        return None;
+    };
+
+    Some(strong.fold_wb(score, -score))
+}
+
+/// Scales an evaluation from the perspective of the given side down in endings that are
+/// harder to win than the material suggests
+pub fn scale(board: &Board, perspective: Color, evaluation: i16) -> i16 {
+    let strong = if evaluation >= 0 {
+        perspective
+    } else {
+        !perspective
+    };
+
+    (evaluation as i32 * scale_factor(board, strong) / 64) as i16
+}
+
+// Scale factor in sixty-fourths for the side ahead
+fn scale_factor(board: &Board, strong: Color) -> i32 {
+    let weak = !strong;
+
+    let strong_pawns = board.pawns() & board.by_color(strong);
+
+    let strong_material = non_pawn_material(board, strong);
+    let weak_material = non_pawn_material(board, weak);
+
+    // Without pawns, being up to a minor piece ahead is rarely enough
+    if strong_pawns.is_empty() && strong_material - weak_material <= material_value(Role::Bishop) {
+        return if strong_material < material_value(Role::Rook) {
+            0
+        } else if weak_material <= material_value(Role::Bishop) {
+            4
+        } else {
+            14
+        };
+    }
+
+    let bishops = board.bishops();
+    let strong_bishops = bishops & board.by_color(strong);
+
+    // A bishop that can't control the promotion square of rook pawns can't drive away the
+    // defending king from the corner
+    if strong_material == material_value(Role::Bishop)
+        && strong_bishops.any()
+        && strong_pawns.any()
+        && [File::A, File::H]
+            .iter()
+            .any(|&file| strong_pawns.is_subset(Bitboard::from_file(file)))
+    {
+        let file = strong_pawns.first().expect("pawn").file();
+        let promotion = Square::from_coords(file, strong.relative_rank(Rank::Eighth));
+        let bishop = strong_bishops.first().expect("bishop");
+
+        if promotion.is_light() != bishop.is_light()
+            && board
+                .king_of(weak)
+                .is_some_and(|king| king.distance(promotion) <= 1)
+        {
+            return 0;
+        }
+    }
+
+    // Opposite-coloured bishop endings, drawish even a pawn or two down. With other pieces
+    // left the bishops matter much less
+    if strong_material == material_value(Role::Bishop)
+        && weak_material == material_value(Role::Bishop)
+        && bishops.count() == 2
+        && (bishops & Bitboard::LIGHT_SQUARES).count() == 1
+    {
+        return 32;
+    }
+
+    64
+}
+
+// The strong side only has to push the lone king to the edge and approach it
+fn kxk(board: &Board, strong: Color, _turn: Color) -> i16 {
+    let strong_king = board.king_of(strong).expect("king");
+    let weak_king = board.king_of(!strong).expect("king");
+
+    let pieces = board.by_color(strong);
+
+    let mut score = non_pawn_material(board, strong)
+        + (board.pawns() & pieces).count() as i16 * material_value(Role::Pawn)
+        + push_to_edge(weak_king)
+        + push_close(strong_king, weak_king);
+
+    let bishops = board.bishops() & pieces;
+
+    if (board.queens() | board.rooks()).intersects(pieces)
+        || (bishops.any() && (board.knights() & pieces).any())
+        || (bishops.intersects(Bitboard::LIGHT_SQUARES)
+            && bishops.intersects(Bitboard::DARK_SQUARES))
+    {
+        score += KNOWN_WIN;
+    }
+
+    score
+}
+
+// Mate can only be forced in a corner of the bishop's colour
+fn kbnk(board: &Board, strong: Color, _turn: Color) -> i16 {
+    let strong_king = board.king_of(strong).expect("king");
+    let weak_king = board.king_of(!strong).expect("king");
+    let bishop = (board.bishops() & board.by_color(strong))
+        .first()
+        .expect("bishop");
+
+    // The corner distance counts towards a1 and h8, mirror for a light-squared bishop
+    let corner = if bishop.is_dark() {
+        push_to_corner(weak_king)
+    } else {
+        push_to_corner(weak_king.flip_horizontal())
+    };
+
+    KNOWN_WIN + push_close(strong_king, weak_king) + 60 * corner
+}
+
+fn kpk(board: &Board, strong: Color, turn: Color) -> i16 {
+    let pawn = (board.pawns() & board.by_color(strong))
+        .first()
+        .expect("pawn");
+
+    // From white's side, with the pawn on the queen side
+    let normalize = |square: Square| {
+        let square = match strong {
+            Color::White => square,
+            Color::Black => square.flip_vertical(),
+        };
+
+        if pawn.file() >= File::E {
+            square.flip_horizontal()
+        } else {
+            square
+        }
+    };
+
+    let strong_king = normalize(board.king_of(strong).expect("king"));
+    let weak_king = normalize(board.king_of(!strong).expect("king"));
+    let pawn = normalize(pawn);
+
+    if !kpk_bitbase().probe(strong_king, pawn, weak_king, turn == strong) {
+        return 0;
+    }
+
+    KNOWN_WIN + material_value(Role::Pawn) + i16::from(pawn.rank()) * 10
+}
+
+fn krkp(board: &Board, strong: Color, turn: Color) -> i16 {
+    let weak = !strong;
+
+    // From white's side, the pawn runs down the board
+    let relative = |square: Square| match strong {
+        Color::White => square,
+        Color::Black => square.flip_vertical(),
+    };
+
+    let strong_king = relative(board.king_of(strong).expect("king"));
+    let weak_king = relative(board.king_of(weak).expect("king"));
+    let rook = relative(board.rooks().first().expect("rook"));
+    let pawn = relative(board.pawns().first().expect("pawn"));
+
+    let queening = Square::from_coords(pawn.file(), Rank::First);
+    let below_pawn = pawn.offset(-8).unwrap_or(pawn);
+
+    let distance = |a: Square, b: Square| a.distance(b) as i16;
+
+    let rook_value = material_value(Role::Rook);
+
+    // The strong king blocking the pawn, or the weak king too far from the pawn and rook
+    if (strong_king.file() == pawn.file() && strong_king.rank() < pawn.rank())
+        || (distance(weak_king, pawn) >= 3 + i16::from(turn == weak)
+            && distance(weak_king, rook) >= 3)
+    {
+        rook_value - distance(strong_king, pawn)
+    }
+    // An advanced pawn supported by its king with the strong king far away
+    else if weak_king.rank() <= Rank::Third
+        && distance(weak_king, pawn) == 1
+        && strong_king.rank() >= Rank::Fourth
+        && distance(strong_king, pawn) > 2 + i16::from(turn == strong)
+    {
+        80 - 8 * distance(strong_king, pawn)
+    } else {
+        200 - 8
+            * (distance(strong_king, below_pawn)
+                - distance(weak_king, below_pawn)
+                - distance(pawn, queening))
+    }
+}
+
+fn kqkp(board: &Board, strong: Color, _turn: Color) -> i16 {
+    let weak = !strong;
+
+    let strong_king = board.king_of(strong).expect("king");
+    let weak_king = board.king_of(weak).expect("king");
+    let pawn = board.pawns().first().expect("pawn");
+
+    let mut score = push_close(strong_king, weak_king);
+
+    // A bishop or rook pawn on the seventh supported by its king can hold the draw
+    if pawn.rank() != weak.relative_rank(Rank::Seventh)
+        || weak_king.distance(pawn) != 1
+        || ![File::A, File::C, File::F, File::H].contains(&pawn.file())
+    {
+        score += material_value(Role::Queen) - material_value(Role::Pawn);
+    }
+
+    score
+}
+
+fn push_to_edge(square: Square) -> i16 {
+    let file = i16::from(square.file()).min(7 - i16::from(square.file()));
+    let rank = i16::from(square.rank()).min(7 - i16::from(square.rank()));
+
+    90 - (7 * file * file / 2 + 7 * rank * rank / 2)
+}
+
+fn push_close(a: Square, b: Square) -> i16 {
+    140 - 20 * a.distance(b) as i16
+}
+
+// Highest in the a1 and h8 corners
+fn push_to_corner(square: Square) -> i16 {
+    (7 - i16::from(square.rank()) - i16::from(square.file())).abs()
+}
+
+fn non_pawn_material(board: &Board, color: Color) -> i16 {
+    let pieces = board.by_color(color);
+
+    [Role::Knight, Role::Bishop, Role::Rook, Role::Queen]
+        .iter()
+        .map(|&role| (board.by_role(role) & pieces).count() as i16 * material_value(role))
+        .sum()
+}
+
+// Piece counts of a side packed four bits each, zero for a lone king
+fn signature(board: &Board, color: Color) -> u32 {
+    let material = board.material_side(color);
+
+    pack(&material)
+}
+
+fn pack(material: &ByRole<u8>) -> u32 {
+    [
+        material.pawn,
+        material.knight,
+        material.bishop,
+        material.rook,
+        material.queen,
+    ]
+    .iter()
+    .enumerate()
+    .map(|(i, &count)| u32::from(count.min(15)) << (4 * i))
+    .sum()
+}
+
+fn endgames() -> &'static HashMap<(u32, u32), Evaluator> {
+    static ENDGAME_TABLE: OnceLock<HashMap<(u32, u32), Evaluator>> = OnceLock::new();
+
+    ENDGAME_TABLE.get_or_init(|| {
+        let parse = |code: &str| {
+            let mut material = ByRole::<u8>::default();
+
+            for c in code.chars().skip(1) {
+                let role = Role::from_char(c).expect("piece letter");
+
+                *material.get_mut(role) += 1;
+            }
+
+            pack(&material)
+        };
+
+        ENDGAMES
+            .iter()
+            .map(|&(strong, weak, evaluator)| ((parse(strong), parse(weak)), evaluator))
+            .collect()
+    })
+}
+
+// Results of the king and pawn against king bitbase, as bit flags so they can be combined
+const INVALID: u8 = 0;
+const UNKNOWN: u8 = 1;
+const DRAW: u8 = 2;
+const WIN: u8 = 4;
+
+// Side to move, pawn on files a to d and ranks 2 to 7, both kings
+const KPK_SIZE: usize = 2 * 24 * 64 * 64;
+
+struct KpkBitbase {
+    wins: Vec<u64>,
+}
+
+impl KpkBitbase {
+    // Whether white wins, with the pawn on the queen side
+    fn probe(
+        &self,
+        white_king: Square,
+        pawn: Square,
+        black_king: Square,
+        white_to_move: bool,
+    ) -> bool {
+        let index = kpk_index(white_to_move, black_king, white_king, pawn);
+
+        self.wins[index / 64] & (1 << (index % 64)) != 0
+    }
+}
+
+fn kpk_index(white_to_move: bool, black_king: Square, white_king: Square, pawn: Square) -> usize {
+    usize::from(white_king)
+        | usize::from(black_king) << 6
+        | usize::from(!white_to_move) << 12
+        | usize::from(pawn.file()) << 13
+        | (6 - usize::from(pawn.rank())) << 15
+}
+
+fn kpk_bitbase() -> &'static KpkBitbase {
+    static KPK: OnceLock<KpkBitbase> = OnceLock::new();
+
+    KPK.get_or_init(|| {
+        let mut results: Vec<u8> = (0..KPK_SIZE).map(kpk_initial).collect();
+
+        // Retrograde iteration until no unknown position can be resolved any more
+        let mut changed = true;
+
+        while changed {
+            changed = false;
+
+            for index in 0..KPK_SIZE {
+                if results[index] == UNKNOWN {
+                    let result = kpk_classify(index, &results);
+
+                    if result != UNKNOWN {
+                        results[index] = result;
+                        changed = true;
+                    }
+                }
+            }
+        }
+
+        let mut wins = vec![0; KPK_SIZE / 64];
+
+        for (index, &result) in results.iter().enumerate() {
+            if result == WIN {
+                wins[index / 64] |= 1 << (index % 64);
+            }
+        }
+
+        KpkBitbase { wins }
+    })
+}
+
+// The squares and side to move stored at an index
+fn kpk_decode(index: usize) -> (Square, Square, bool, Square) {
+    let white_king = Square::new((index & 0x3f) as u32);
+    let black_king = Square::new(((index >> 6) & 0x3f) as u32);
+    let white_to_move = (index >> 12) & 1 == 0;
+    let pawn = Square::from_coords(
+        File::new(((index >> 13) & 3) as u32),
+        Rank::new(6 - (index >> 15) as u32),
+    );
+
+    (white_king, black_king, white_to_move, pawn)
+}
+
+fn kpk_initial(index: usize) -> u8 {
+    let (white_king, black_king, white_to_move, pawn) = kpk_decode(index);
+
+    let push = pawn.offset(8).expect("pawn below the eighth rank");
+
+    if white_king.distance(black_king) <= 1
+        || white_king == pawn
+        || black_king == pawn
+        || (white_to_move && pawn_attacks(Color::White, pawn).contains(black_king))
+    {
+        INVALID
+    }
+    // The pawn promotes safely
+    else if white_to_move
+        && pawn.rank() == Rank::Seventh
+        && white_king != push
+        && black_king != push
+        && (black_king.distance(push) > 1 || king_attacks(white_king).contains(push))
+    {
+        WIN
+    }
+    // Stalemate, or the pawn is lost
+    else if !white_to_move
+        && ((king_attacks(black_king)
+            & !(king_attacks(white_king) | pawn_attacks(Color::White, pawn)))
+        .is_empty()
+            || (king_attacks(black_king) & !king_attacks(white_king)).contains(pawn))
+    {
+        DRAW
+    } else {
+        UNKNOWN
+    }
+}
+
+fn kpk_classify(index: usize, results: &[u8]) -> u8 {
+    let (white_king, black_king, white_to_move, pawn) = kpk_decode(index);
+
+    let mut result = INVALID;
+
+    if white_to_move {
+        for to in king_attacks(white_king) {
+            result |= results[kpk_index(false, black_king, to, pawn)];
+        }
+
+        if pawn.rank() < Rank::Seventh {
+            let push = pawn.offset(8).expect("pawn below the eighth rank");
+
+            result |= results[kpk_index(false, black_king, white_king, push)];
+
+            if pawn.rank() == Rank::Second && push != white_king && push != black_king {
+                let double_push = push.offset(8).expect("double push square");
+
+                result |= results[kpk_index(false, black_king, white_king, double_push)];
+            }
+        }
+    } else {
+        for to in king_attacks(black_king) {
+            result |= results[kpk_index(true, to, white_king, pawn)];
+        }
+    }
+
+    // The side to move picks its best reply, white wants a win and black a draw
+    let (good, bad) = if white_to_move {
+        (WIN, DRAW)
+    } else {
+        (DRAW, WIN)
+    };
+
+    if result & good != 0 {
+        good
+    } else if result & UNKNOWN != 0 {
+        UNKNOWN
+    } else {
+        bad
+    }
+}
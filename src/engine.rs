@@ -1,107 +1,397 @@
-use crate::{search, timer};
-use shakmaty::{zobrist::Zobrist64, Chess, Color, Position};
+use crate::correction::CorrectionHistory;
+use crate::history::History;
+use crate::params::{InternalIterative, SearchParams};
+use crate::random::Rng;
+use crate::skill::{self, Skill};
+use crate::variants::SearchPosition;
+use crate::{book, nnue, search, tablebase, timer};
+use shakmaty::variant::{Variant, VariantPosition};
+use shakmaty::{zobrist::Zobrist64, CastlingMode, Color, Position};
 use std::sync::{
     atomic::{AtomicBool, Ordering},
     Arc, Mutex,
 };
-use std::thread;
+use std::thread::{self, JoinHandle};
 
 const MAX_TRANSPOSITION_TABLE_SIZE_MB: usize = 256;
 pub const TRANSPOSITION_TABLE_LENGTH: usize =
     MAX_TRANSPOSITION_TABLE_SIZE_MB * 1_000_000 / size_of::<Option<search::Node>>();
 
 pub struct Engine {
-    pub board: Chess,
+    pub board: VariantPosition,
+    pub variant: Variant,
+    pub castling_mode: CastlingMode,
     debug: Arc<AtomicBool>,
     searching: Arc<AtomicBool>,
     pondering: Arc<AtomicBool>,
     pub position_history: Vec<Zobrist64>,
     transposition_table: Arc<Mutex<Vec<Option<search::Node>>>>,
+    // Move ordering history and evaluation corrections, kept between the searches of a game
+    history: Arc<Mutex<History>>,
+    correction: Arc<Mutex<CorrectionHistory>>,
+    search_thread: Option<JoinHandle<()>>,
+    network: Option<Arc<nnue::Network>>,
+    use_nnue: bool,
+    tablebase: Option<Arc<tablebase::Tablebase>>,
+    tb_probe_limit: u32,
+    book: Option<book::Book>,
+    own_book: bool,
+    book_depth: u32,
+    book_best_move: bool,
+    rng: Rng,
+    params: SearchParams,
+    contempt: i16,
+    analysis_contempt: bool,
+    multi_pv: usize,
+    skill_level: i16,
+    limit_strength: bool,
+    elo: u16,
 }
 
 impl Engine {
     pub fn new() -> Engine {
+        let network = nnue::Network::embedded().map(Arc::new);
+
         Engine {
-            board: Chess::new(),
+            board: VariantPosition::new(Variant::Chess),
+            variant: Variant::Chess,
+            castling_mode: CastlingMode::Standard,
             debug: Arc::new(AtomicBool::new(true)),
             searching: Arc::new(AtomicBool::new(false)),
             pondering: Arc::new(AtomicBool::new(false)),
             position_history: Vec::with_capacity(512),
             transposition_table: Arc::new(Mutex::new(vec![None; TRANSPOSITION_TABLE_LENGTH])),
+            history: Arc::new(Mutex::new(History::new())),
+            correction: Arc::new(Mutex::new(CorrectionHistory::new())),
+            search_thread: None,
+            use_nnue: network.is_some(),
+            network,
+            tablebase: None,
+            tb_probe_limit: 7,
+            book: None,
+            own_book: false,
+            book_depth: 20,
+            book_best_move: false,
+            rng: Rng::from_time(0),
+            params: SearchParams::default(),
+            contempt: 0,
+            analysis_contempt: false,
+            multi_pv: 1,
+            skill_level: skill::MAX_LEVEL,
+            limit_strength: false,
+            elo: skill::MAX_ELO,
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn search(
-        &self,
+        &mut self,
         white_time: Option<u64>,
         black_time: Option<u64>,
         white_increment: Option<u64>,
         black_increment: Option<u64>,
         move_time: Option<u64>,
         depth: Option<i16>,
+        nodes: Option<u64>,
         infinite: bool,
     ) {
-        self.searching.store(true, Ordering::Relaxed);
+        // A go while searching finishes the running search first, so every go gets its own bestmove
+        self.stop();
+
+        // An infinite search must not end before stop, so analysis never plays from the book
+        if !infinite {
+            if let Some(book_move) = self.book_move() {
+                println!("bestmove {}", book_move.to_uci(self.castling_mode));
+
+                return;
+            }
+        }
 
-        let board_clone = self.board.clone();
+        // A fresh flag per search, so a timer left over from the previous search can't stop this one
+        self.searching = Arc::new(AtomicBool::new(true));
 
         let debug_clone = Arc::clone(&self.debug);
         let searching_clone = Arc::clone(&self.searching);
-        let pondering_clone = Arc::clone(&self.searching);
 
-        let mut position_history_clone = self.position_history.clone();
+        let mut searcher = search::Searcher::new(searching_clone, debug_clone);
 
-        let mut transposition_table_clone = Arc::clone(&self.transposition_table);
+        searcher.max_depth = depth;
+        searcher.max_nodes = nodes;
+        searcher.multi_pv = self.multi_pv;
+        searcher.params = self.params.clone();
+        searcher.castling_mode = self.castling_mode;
 
-        let mut searcher = search::Searcher {
-            nodes: 0,
-            searching: searching_clone,
-            _pondering: pondering_clone,
-            debug: debug_clone,
-            max_depth: depth,
-            best_root_move: None,
-        };
+        // Analysis should show the objective score unless asked otherwise
+        if !infinite || self.analysis_contempt {
+            searcher.contempt = Some(self.contempt);
+        }
+        searcher.tablebase = self.tablebase.clone();
+        searcher.tb_probe_limit = self.tb_probe_limit;
 
-        thread::spawn(move || {
-            searcher.search(
-                board_clone,
-                &mut position_history_clone,
-                &mut transposition_table_clone,
-            )
-        });
+        let skill = self.skill();
+
+        if let Some(skill) = skill {
+            skill.limit(&mut searcher);
+        }
+
+        // The network is trained on standard chess only
+        if self.variant == Variant::Chess {
+            searcher.nnue = self.nnue();
+        }
+
+        match self.board.clone() {
+            VariantPosition::Chess(board) => self.spawn_search(searcher, skill, board),
+            VariantPosition::Atomic(board) => self.spawn_search(searcher, skill, board),
+            VariantPosition::Antichess(board) => self.spawn_search(searcher, skill, board),
+            VariantPosition::KingOfTheHill(board) => self.spawn_search(searcher, skill, board),
+            VariantPosition::ThreeCheck(board) => self.spawn_search(searcher, skill, board),
+            VariantPosition::Crazyhouse(board) => self.spawn_search(searcher, skill, board),
+            VariantPosition::RacingKings(board) => self.spawn_search(searcher, skill, board),
+            VariantPosition::Horde(board) => self.spawn_search(searcher, skill, board),
+        }
 
         let searching_clone = Arc::clone(&self.searching);
 
         if !infinite {
             if let Some(move_time) = move_time {
                 thread::spawn(move || timer::search_for_ms(move_time, searching_clone));
-            } else if self.board.turn() == Color::White && white_time.is_some() {
-                let remaining = white_time.unwrap();
+            } else if let (Color::White, Some(remaining)) = (self.board.turn(), white_time) {
                 let increment = white_increment.unwrap_or(0);
 
-                let move_time = remaining / 20 + increment / 2;
+                let move_time = timer::allocate(remaining, increment);
 
                 thread::spawn(move || timer::search_for_ms(move_time, searching_clone));
-            } else if self.board.turn() == Color::Black && black_time.is_some() {
-                let remaining = black_time.unwrap();
+            } else if let (Color::Black, Some(remaining)) = (self.board.turn(), black_time) {
                 let increment = black_increment.unwrap_or(0);
 
-                let move_time = remaining / 20 + increment / 2;
+                let move_time = timer::allocate(remaining, increment);
 
                 thread::spawn(move || timer::search_for_ms(move_time, searching_clone));
             }
         }
     }
 
+    fn spawn_search<P: SearchPosition>(
+        &mut self,
+        mut searcher: search::Searcher,
+        skill: Option<Skill>,
+        board: P,
+    ) {
+        let mut position_history_clone = self.position_history.clone();
+
+        let mut transposition_table_clone = Arc::clone(&self.transposition_table);
+
+        let history_clone = Arc::clone(&self.history);
+        let correction_clone = Arc::clone(&self.correction);
+
+        let castling_mode = self.castling_mode;
+
+        let mut rng = Rng::new(self.rng.next_u64());
+
+        self.search_thread = Some(thread::spawn(move || {
+            let mut history = history_clone.lock().unwrap();
+            let mut correction = correction_clone.lock().unwrap();
+
+            std::mem::swap(&mut searcher.history, &mut *history);
+            std::mem::swap(&mut searcher.correction, &mut *correction);
+
+            let result = searcher.search(
+                board.clone(),
+                &mut position_history_clone,
+                &mut transposition_table_clone,
+            );
+
+            std::mem::swap(&mut searcher.history, &mut *history);
+            std::mem::swap(&mut searcher.correction, &mut *correction);
+
+            let best_move = match skill {
+                Some(skill) => skill.pick(&result.lines, &mut rng),
+                None => result.best_move,
+            };
+
+            // A search stopped before finishing the first iteration still has to answer
+            let best_move = best_move
+                .or(result.best_move)
+                .or_else(|| board.legal_moves().first().copied());
+
+            match best_move {
+                Some(best_move) => println!("bestmove {}", best_move.to_uci(castling_mode)),
+                None => println!("bestmove 0000"),
+            }
+        }));
+    }
+
+    fn skill(&self) -> Option<Skill> {
+        if self.limit_strength {
+            Skill::from_elo(self.elo)
+        } else {
+            Skill::from_level(self.skill_level)
+        }
+    }
+
+    fn book_move(&mut self) -> Option<shakmaty::Move> {
+        // Polyglot books only cover standard chess
+        let book = self
+            .book
+            .as_ref()
+            .filter(|_| self.own_book && self.castling_mode == CastlingMode::Standard)?;
+
+        let VariantPosition::Chess(ref board) = self.board else {
+            return None;
+        };
+
+        if board.fullmoves().get() > self.book_depth {
+            return None;
+        }
+
+        book.probe(board, self.book_best_move, &mut self.rng)
+    }
+
+    fn nnue(&self) -> Option<nnue::AccumulatorStack> {
+        match self.network {
+            Some(ref network) if self.use_nnue => {
+                Some(nnue::AccumulatorStack::new(Arc::clone(network)))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn set_option(&mut self, name: &str, value: &str) {
+        self.stop();
+
+        match name.to_lowercase().as_str() {
+            "evalfile" => match nnue::Network::load(value) {
+                Ok(network) => {
+                    println!(
+                        "info string loaded network {value} with {} hidden neurons",
+                        network.hidden_size()
+                    );
+
+                    self.network = Some(Arc::new(network));
+                }
+                Err(error) => println!("info string failed to load network {error}"),
+            },
+            "use nnue" => {
+                self.use_nnue = value == "true";
+
+                if self.use_nnue && self.network.is_none() {
+                    println!("info string no network loaded, using the classical evaluation");
+                }
+            }
+            "syzygypath" => {
+                if value.is_empty() || value == "<empty>" {
+                    self.tablebase = None;
+
+                    return;
+                }
+
+                match tablebase::Tablebase::open(value) {
+                    Ok(tablebase) => {
+                        println!(
+                            "info string found tables up to {} pieces in {} directories",
+                            tablebase.max_pieces(),
+                            tablebase.directories().len()
+                        );
+
+                        self.tablebase = Some(Arc::new(tablebase));
+                    }
+                    Err(error) => println!("info string failed to open tablebase {error}"),
+                }
+            }
+            "syzygyprobelimit" => match value.parse::<u32>() {
+                Ok(limit) => self.tb_probe_limit = limit.min(7),
+                Err(_) => println!("info string invalid value {value} for {name}"),
+            },
+            "uci_variant" => match Variant::from_uci(&value.to_lowercase()) {
+                Ok(variant) => {
+                    self.variant = variant;
+
+                    self.reset();
+                }
+                Err(_) => println!("info string unknown variant {value}"),
+            },
+            "uci_chess960" => {
+                self.castling_mode = CastlingMode::from_chess960(value == "true");
+            }
+            "contempt" => match value.parse::<i16>() {
+                Ok(contempt) => self.contempt = contempt.clamp(-100, 100),
+                Err(_) => println!("info string invalid value {value} for {name}"),
+            },
+            "analysis contempt" => self.analysis_contempt = value == "true",
+            "multipv" => match value.parse::<usize>() {
+                Ok(multi_pv) => self.multi_pv = multi_pv.clamp(1, 64),
+                Err(_) => println!("info string invalid value {value} for {name}"),
+            },
+            "skill level" => match value.parse::<i16>() {
+                Ok(level) => self.skill_level = level.clamp(0, skill::MAX_LEVEL),
+                Err(_) => println!("info string invalid value {value} for {name}"),
+            },
+            "uci_limitstrength" => self.limit_strength = value == "true",
+            "uci_elo" => match value.parse::<u16>() {
+                Ok(elo) => self.elo = elo.clamp(skill::MIN_ELO, skill::MAX_ELO),
+                Err(_) => println!("info string invalid value {value} for {name}"),
+            },
+            "ownbook" => self.own_book = value == "true",
+            "bookfile" => match book::Book::load(value) {
+                Ok(book) => {
+                    println!(
+                        "info string loaded book {value} with {} entries",
+                        book.len()
+                    );
+
+                    self.book = Some(book);
+                }
+                Err(error) => println!("info string failed to load book {error}"),
+            },
+            "bookdepth" => match value.parse::<u32>() {
+                Ok(depth) => self.book_depth = depth,
+                Err(_) => println!("info string invalid value {value} for {name}"),
+            },
+            "bookbestmove" => self.book_best_move = value == "true",
+            "internaliterative" => match InternalIterative::from_name(value) {
+                Ok(mode) => self.params.internal_iterative = mode,
+                Err(error) => println!("info string {error}"),
+            },
+            #[cfg(feature = "tune")]
+            name if crate::params::TUNABLES
+                .iter()
+                .any(|t| t.name.eq_ignore_ascii_case(name)) =>
+            {
+                match value.parse::<i16>() {
+                    Ok(value) => {
+                        if let Err(error) = self.params.set(name, value) {
+                            println!("info string {error}");
+                        }
+                    }
+                    Err(_) => println!("info string invalid value {value} for {name}"),
+                }
+            }
+            _ => println!("info string unknown option {name}"),
+        }
+    }
+
+    /// The search parameters in the SPSA tuner format
+    #[cfg(feature = "tune")]
+    pub fn spsa(&self) -> String {
+        crate::params::spsa_text(&self.params)
+    }
+
     pub fn debug(&mut self, enable: &bool) {
         self.debug.store(*enable, Ordering::Relaxed);
     }
 
+    /// Sets up a position from the GUI, keeping the transposition table
+    pub fn set_position(&mut self, board: VariantPosition, position_history: Vec<Zobrist64>) {
+        self.stop();
+
+        self.board = board;
+        self.position_history = position_history;
+    }
+
     pub fn reset(&mut self) {
-        self.searching.store(false, Ordering::Relaxed);
-        self.pondering.store(false, Ordering::Relaxed);
+        self.stop();
 
-        self.board = Chess::new();
+        self.board = VariantPosition::new(self.variant);
 
         self.position_history.clear();
 
@@ -111,11 +401,19 @@ impl Engine {
         for node in self.transposition_table.lock().unwrap().iter_mut() {
             *node = None;
         }
+
+        *self.history.lock().unwrap() = History::new();
+        *self.correction.lock().unwrap() = CorrectionHistory::new();
     }
 
+    /// Stops the running search and waits for it to print its bestmove
     pub fn stop(&mut self) {
         self.searching.store(false, Ordering::Relaxed);
         self.pondering.store(false, Ordering::Relaxed);
+
+        if let Some(search_thread) = self.search_thread.take() {
+            let _ = search_thread.join();
+        }
     }
 
     pub fn ponder_hit(&mut self) {
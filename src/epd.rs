@@ -0,0 +1,102 @@
+use shakmaty::fen::Fen;
+use shakmaty::san::SanPlus;
+use shakmaty::{CastlingMode, Chess, Move};
+
+/// A position from an EPD line with its operations, such as `bm Qg6; id "WAC.001";`
+pub struct Epd {
+    pub board: Chess,
+    pub operations: Vec<(String, String)>,
+}
+
+impl Epd {
+    /// Reads the first four FEN fields, optional clocks and the operations
+    pub fn parse(line: &str) -> Option<Epd> {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+
+        if tokens.len() < 4 {
+            return None;
+        }
+
+        let mut fen_length = 4;
+
+        while fen_length < 6
+            && tokens
+                .get(fen_length)
+                .is_some_and(|t| t.parse::<u32>().is_ok())
+        {
+            fen_length += 1;
+        }
+
+        let board: Chess = Fen::from_ascii(tokens[..fen_length].join(" ").as_bytes())
+            .ok()?
+            .into_position(CastlingMode::Standard)
+            .ok()?;
+
+        let rest = tokens[fen_length..].join(" ");
+
+        let mut operations: Vec<(String, String)> = Vec::new();
+
+        let mut operation = String::new();
+        let mut quoted = false;
+
+        for c in rest.chars() {
+            match c {
+                '"' => {
+                    quoted = !quoted;
+                    operation.push(c);
+                }
+                ';' if !quoted => {
+                    push_operation(&mut operations, &operation);
+                    operation.clear();
+                }
+                _ => operation.push(c),
+            }
+        }
+
+        push_operation(&mut operations, &operation);
+
+        Some(Epd { board, operations })
+    }
+
+    pub fn operation(&self, opcode: &str) -> Option<&str> {
+        self.operations
+            .iter()
+            .find(|(o, _)| o == opcode)
+            .map(|(_, operand)| operand.as_str())
+    }
+
+    /// Legal moves listed in SAN by an operation such as `bm` or `am`
+    pub fn moves(&self, opcode: &str) -> Vec<Move> {
+        self.operation(opcode)
+            .map(|operand| {
+                operand
+                    .split_whitespace()
+                    .filter_map(|san| parse_san(&self.board, san))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+fn push_operation(operations: &mut Vec<(String, String)>, operation: &str) {
+    let operation = operation.trim();
+
+    if operation.is_empty() {
+        return;
+    }
+
+    let (opcode, operand) = operation.split_once(' ').unwrap_or((operation, ""));
+
+    operations.push((
+        opcode.to_string(),
+        operand.trim().trim_matches('"').to_string(),
+    ));
+}
+
+pub fn parse_san(board: &Chess, san: &str) -> Option<Move> {
+    SanPlus::from_ascii(san.trim_end_matches(['!', '?']).as_bytes())
+        .ok()?
+        .san
+        .to_move(board)
+        .ok()
+}
@@ -0,0 +1,171 @@
+use crate::epd::{parse_san, Epd};
+use crate::search::{Node, Searcher};
+use crate::timer;
+use shakmaty::san::SanPlus;
+use shakmaty::zobrist::Zobrist64;
+use shakmaty::{EnPassantMode, Move, Position};
+use std::fs;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+const TRANSPOSITION_TABLE_SIZE_MB: usize = 64;
+const TRANSPOSITION_TABLE_LENGTH: usize =
+    TRANSPOSITION_TABLE_SIZE_MB * 1_000_000 / size_of::<Option<Node>>();
+
+enum Limit {
+    MoveTime(u64),
+    Nodes(u64),
+    Depth(i16),
+}
+
+/// Usage: epdtest <epd> [movetime|nodes|depth] [value]
+///
+/// Positions pass when the best move is one of `bm` and none of `am`. Positions with
+/// `c0` move scores, as in STS (`c0 "Qg6=10, Rf1=5";`), score the points of the move found.
+pub fn run(args: &[String]) {
+    let Some(epd_path) = args.first() else {
+        eprintln!("usage: reggz epdtest <epd> [movetime|nodes|depth] [value]");
+        return;
+    };
+
+    let value = args.get(2).and_then(|v| v.parse::<u64>().ok());
+
+    let limit = match (args.get(1).map(String::as_str), value) {
+        (None, _) => Limit::MoveTime(1000),
+        (Some("movetime"), Some(value)) => Limit::MoveTime(value),
+        (Some("nodes"), Some(value)) => Limit::Nodes(value),
+        (Some("depth"), Some(value)) => Limit::Depth(value.min(i16::MAX as u64) as i16),
+        _ => {
+            eprintln!("usage: reggz epdtest <epd> [movetime|nodes|depth] [value]");
+            return;
+        }
+    };
+
+    let text = match fs::read_to_string(epd_path) {
+        Ok(text) => text,
+        Err(error) => {
+            eprintln!("failed to read {epd_path}: {error}");
+            return;
+        }
+    };
+
+    let mut transposition_table = Arc::new(Mutex::new(vec![None; TRANSPOSITION_TABLE_LENGTH]));
+
+    let mut positions: u32 = 0;
+    let mut passed: u32 = 0;
+    let mut points: u32 = 0;
+    let mut max_points: u32 = 0;
+
+    for (line_number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let Some(epd) = Epd::parse(line) else {
+            eprintln!("line {}: invalid epd", line_number + 1);
+            continue;
+        };
+
+        for node in transposition_table.lock().unwrap().iter_mut() {
+            *node = None;
+        }
+
+        let id = epd
+            .operation("id")
+            .map_or_else(|| format!("line {}", line_number + 1), str::to_string);
+
+        let Some(best_move) = search(&epd, &limit, &mut transposition_table) else {
+            println!("{id:<24} no legal moves");
+            continue;
+        };
+
+        let best_moves = epd.moves("bm");
+        let avoid_moves = epd.moves("am");
+        let move_scores = move_scores(&epd);
+
+        positions += 1;
+
+        // Unparseable bm moves fail the position rather than pass it
+        let pass = (epd.operation("bm").is_none() || best_moves.contains(&best_move))
+            && !avoid_moves.contains(&best_move)
+            && (move_scores.is_empty() || move_scores.iter().any(|(m, _)| *m == best_move));
+
+        let position_points = move_scores
+            .iter()
+            .find(|(m, _)| *m == best_move)
+            .map_or(0, |(_, points)| *points);
+
+        if pass {
+            passed += 1;
+        }
+
+        points += position_points;
+        max_points += move_scores
+            .iter()
+            .map(|(_, points)| *points)
+            .max()
+            .unwrap_or(0);
+
+        let san = SanPlus::from_move(epd.board.clone(), best_move);
+
+        if move_scores.is_empty() {
+            println!("{id:<24} {san:<8} {}", if pass { "pass" } else { "fail" });
+        } else {
+            println!(
+                "{id:<24} {san:<8} {} {position_points}",
+                if pass { "pass" } else { "fail" }
+            );
+        }
+    }
+
+    println!("passed {passed}/{positions}");
+
+    if max_points > 0 {
+        println!("score {points}/{max_points}");
+    }
+}
+
+fn search(
+    epd: &Epd,
+    limit: &Limit,
+    transposition_table: &mut Arc<Mutex<Vec<Option<Node>>>>,
+) -> Option<Move> {
+    let searching = Arc::new(AtomicBool::new(true));
+
+    let mut searcher = Searcher::new(Arc::clone(&searching), Arc::new(AtomicBool::new(false)));
+
+    match *limit {
+        Limit::MoveTime(move_time) => {
+            thread::spawn(move || timer::search_for_ms(move_time, searching));
+        }
+        Limit::Nodes(nodes) => searcher.max_nodes = Some(nodes),
+        Limit::Depth(depth) => searcher.max_depth = Some(depth),
+    }
+
+    let mut position_history: Vec<Zobrist64> = vec![epd.board.zobrist_hash(EnPassantMode::Legal)];
+
+    searcher
+        .search(
+            epd.board.clone(),
+            &mut position_history,
+            transposition_table,
+        )
+        .best_move
+}
+
+// Moves and their points from an STS-style c0 operation
+fn move_scores(epd: &Epd) -> Vec<(Move, u32)> {
+    let Some(operand) = epd.operation("c0") else {
+        return Vec::new();
+    };
+
+    operand
+        .split(',')
+        .filter_map(|entry| {
+            let (san, points) = entry.trim().split_once('=')?;
+
+            Some((parse_san(&epd.board, san)?, points.trim().parse().ok()?))
+        })
+        .collect()
+}
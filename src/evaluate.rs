@@ -1,4 +1,10 @@
-use shakmaty::{Bitboard, Board, Chess, Position};
+use crate::endgame;
+use shakmaty::{Bitboard, Board, Chess, Color, Position, Role};
+
+// Pawn, knight, bishop, rook, queen
+static MATERIAL_VALUES: [i16; 5] = [100, 320, 320, 500, 900];
+
+static BISHOP_PAIR_VALUE: i16 = 50;
 
 #[rustfmt::skip]
 static PAWN_VALUES: [i16; 64] = [
@@ -144,142 +150,172 @@ static KING_VALUES_LATE: [i16; 64] = [
     -53, -34, -21, -11, -28, -14, -24, -43
 ];
 
+// Layout of the evaluation weight vector
+pub const MATERIAL_OFFSET: usize = 0;
+pub const BISHOP_PAIR_OFFSET: usize = MATERIAL_OFFSET + 5;
+pub const PIECE_SQUARE_OFFSET: usize = BISHOP_PAIR_OFFSET + 1;
+pub const PIECE_SQUARE_LATE_OFFSET: usize = PIECE_SQUARE_OFFSET + 6 * 64;
+pub const WEIGHT_COUNT: usize = PIECE_SQUARE_LATE_OFFSET + 6 * 64;
+
+pub static WEIGHTS: [i16; WEIGHT_COUNT] = build_weights();
+
+const ROLES: [Role; 6] = [
+    Role::Pawn,
+    Role::Knight,
+    Role::Bishop,
+    Role::Rook,
+    Role::Queen,
+    Role::King,
+];
+
+const fn build_weights() -> [i16; WEIGHT_COUNT] {
+    let mut weights = [0; WEIGHT_COUNT];
+
+    let early = [
+        PAWN_VALUES,
+        KNIGHT_VALUES,
+        BISHOP_VALUES,
+        ROOK_VALUES,
+        QUEEN_VALUES,
+        KING_VALUES,
+    ];
+    let late = [
+        PAWN_VALUES_LATE,
+        KNIGHT_VALUES_LATE,
+        BISHOP_VALUES_LATE,
+        ROOK_VALUES_LATE,
+        QUEEN_VALUES_LATE,
+        KING_VALUES_LATE,
+    ];
+
+    let mut i = 0;
+    while i < 5 {
+        weights[MATERIAL_OFFSET + i] = MATERIAL_VALUES[i];
+        i += 1;
+    }
+
+    weights[BISHOP_PAIR_OFFSET] = BISHOP_PAIR_VALUE;
+
+    let mut i = 0;
+    while i < 6 * 64 {
+        weights[PIECE_SQUARE_OFFSET + i] = early[i / 64][i % 64];
+        weights[PIECE_SQUARE_LATE_OFFSET + i] = late[i / 64][i % 64];
+        i += 1;
+    }
+
+    weights
+}
+
+/// Evaluates a standard chess position from white's perspective, with known endings
+/// evaluated by their own rules
 #[inline(always)]
 pub fn evaluate(board: &Chess) -> i16 {
+    if let Some(score) = endgame::evaluate(board.board(), board.turn()) {
+        return score;
+    }
+
+    endgame::scale(
+        board.board(),
+        Color::White,
+        evaluate_with(board.board(), &WEIGHTS),
+    )
+}
+
+/// Evaluates the pieces on the board from white's perspective, shared by all variants
+#[inline(always)]
+pub fn evaluate_board(board: &Board) -> i16 {
+    evaluate_with(board, &WEIGHTS)
+}
+
+/// Material value of a piece, zero for the king
+pub fn material_value(role: Role) -> i16 {
+    match role {
+        Role::King => 0,
+        _ => WEIGHTS[MATERIAL_OFFSET + role as usize - 1],
+    }
+}
+
+/// Evaluates the board from white's perspective using the given weight vector
+#[inline(always)]
+pub fn evaluate_with(board: &Board, weights: &[i16; WEIGHT_COUNT]) -> i16 {
     let mut score: i16 = 0;
 
-    let bitboard: &Board = board.board();
+    for_each_term(board, |index, coefficient| {
+        score += weights[index] * coefficient;
+    });
 
-    score += 100 * (bitboard.pawns() & bitboard.white()).count() as i16; // White pawns
-    score -= 100 * (bitboard.pawns() & bitboard.black()).count() as i16; // Black pawns
+    score
+}
 
-    score += 320 * (bitboard.bishops() & bitboard.white()).count() as i16; // White bishops
-    score -= 320 * (bitboard.bishops() & bitboard.black()).count() as i16; // Black bishops
+/// Collects the coefficient of every weight that contributes to the evaluation.
+/// The evaluation is linear, so it equals the dot product of these with the weights.
+pub fn features(board: &Chess) -> Vec<(u16, i8)> {
+    let mut features: Vec<(u16, i8)> = Vec::with_capacity(64);
 
-    score += 320 * (bitboard.knights() & bitboard.white()).count() as i16; // White knights
-    score -= 320 * (bitboard.knights() & bitboard.black()).count() as i16; // Black bishops
+    for_each_term(board.board(), |index, coefficient| {
+        match features.iter_mut().find(|(i, _)| *i as usize == index) {
+            Some((_, c)) => *c += coefficient as i8,
+            None => features.push((index as u16, coefficient as i8)),
+        }
+    });
 
-    score += 500 * (bitboard.rooks() & bitboard.white()).count() as i16; // White rooks
-    score -= 500 * (bitboard.rooks() & bitboard.black()).count() as i16; // Black rooks
+    features.retain(|(_, c)| *c != 0);
 
-    score += 900 * (bitboard.queens() & bitboard.white()).count() as i16; // White queen
-    score -= 900 * (bitboard.queens() & bitboard.black()).count() as i16; // Black queen
+    features
+}
+
+#[inline(always)]
+fn for_each_term(bitboard: &Board, mut term: impl FnMut(usize, i16)) {
+    for (i, role) in ROLES[..5].iter().enumerate() {
+        let pieces = bitboard.by_role(*role);
+
+        term(
+            MATERIAL_OFFSET + i,
+            (pieces & bitboard.white()).count() as i16 - (pieces & bitboard.black()).count() as i16,
+        );
+    }
 
     // Both bishops alive
     if (bitboard.bishops() & bitboard.white()).count() == 2 {
-        score += 50;
+        term(BISHOP_PAIR_OFFSET, 1);
     }
 
     if (bitboard.bishops() & bitboard.black()).count() == 2 {
-        score -= 50;
+        term(BISHOP_PAIR_OFFSET, -1);
     }
 
     // Early/mid game
-    if bitboard.queens().count()
+    let offset = if bitboard.queens().count()
         + bitboard.rooks().count()
         + bitboard.knights().count()
         + bitboard.bishops().count()
         > 4
     {
-        // Pawn positions
-        score += get_positional_value(bitboard.pawns() & bitboard.white(), PAWN_VALUES);
-        score -= get_positional_value(
-            (bitboard.pawns() & bitboard.black()).flip_vertical(),
-            PAWN_VALUES,
-        );
-
-        // Knight positions
-        score += get_positional_value(bitboard.knights() & bitboard.white(), KNIGHT_VALUES);
-        score -= get_positional_value(
-            (bitboard.knights() & bitboard.black()).flip_vertical(),
-            KNIGHT_VALUES,
-        );
-
-        // Bishop positions
-        score += get_positional_value(bitboard.bishops() & bitboard.white(), BISHOP_VALUES);
-        score -= get_positional_value(
-            (bitboard.bishops() & bitboard.black()).flip_vertical(),
-            BISHOP_VALUES,
-        );
-
-        // Rook positions
-        score += get_positional_value(bitboard.rooks() & bitboard.white(), ROOK_VALUES);
-        score -= get_positional_value(
-            (bitboard.rooks() & bitboard.black()).flip_vertical(),
-            ROOK_VALUES,
-        );
-
-        // Queen positions
-        score += get_positional_value(bitboard.queens() & bitboard.white(), QUEEN_VALUES);
-        score -= get_positional_value(
-            (bitboard.queens() & bitboard.black()).flip_vertical(),
-            QUEEN_VALUES,
-        );
-
-        // King positions
-        score += get_positional_value(bitboard.kings() & bitboard.white(), KING_VALUES);
-        score -= get_positional_value(
-            (bitboard.kings() & bitboard.black()).flip_vertical(),
-            KING_VALUES,
-        );
+        PIECE_SQUARE_OFFSET
     }
     // End game
     else {
-        // Pawn positions
-        score += get_positional_value(bitboard.pawns() & bitboard.white(), PAWN_VALUES_LATE);
-        score -= get_positional_value(
-            (bitboard.pawns() & bitboard.black()).flip_vertical(),
-            PAWN_VALUES_LATE,
-        );
-
-        // Knight positions
-        score += get_positional_value(bitboard.knights() & bitboard.white(), KNIGHT_VALUES_LATE);
-        score -= get_positional_value(
-            (bitboard.knights() & bitboard.black()).flip_vertical(),
-            KNIGHT_VALUES_LATE,
-        );
-
-        // Bishop positions
-        score += get_positional_value(bitboard.bishops() & bitboard.white(), BISHOP_VALUES_LATE);
-        score -= get_positional_value(
-            (bitboard.bishops() & bitboard.black()).flip_vertical(),
-            BISHOP_VALUES_LATE,
-        );
-
-        // Rook positions
-        score += get_positional_value(bitboard.rooks() & bitboard.white(), ROOK_VALUES_LATE);
-        score -= get_positional_value(
-            (bitboard.rooks() & bitboard.black()).flip_vertical(),
-            ROOK_VALUES_LATE,
-        );
-
-        // Queen positions
-        score += get_positional_value(bitboard.queens() & bitboard.white(), QUEEN_VALUES_LATE);
-        score -= get_positional_value(
-            (bitboard.queens() & bitboard.black()).flip_vertical(),
-            QUEEN_VALUES_LATE,
-        );
-
-        // King positions
-        score += get_positional_value(bitboard.kings() & bitboard.white(), KING_VALUES_LATE);
-        score -= get_positional_value(
-            (bitboard.kings() & bitboard.black()).flip_vertical(),
-            KING_VALUES_LATE,
+        PIECE_SQUARE_LATE_OFFSET
+    };
+
+    for (i, role) in ROLES.iter().enumerate() {
+        let pieces = bitboard.by_role(*role);
+
+        positional_terms(pieces & bitboard.white(), offset + i * 64, 1, &mut term);
+        positional_terms(
+            (pieces & bitboard.black()).flip_vertical(),
+            offset + i * 64,
+            -1,
+            &mut term,
         );
     }
-
-    score
 }
 
 #[inline(always)]
-fn get_positional_value(pieces: Bitboard, piece_square_table: [i16; 64]) -> i16 {
-    let mut score: i16 = 0;
-
+fn positional_terms(pieces: Bitboard, offset: usize, sign: i16, term: &mut impl FnMut(usize, i16)) {
     for square in pieces {
         let square_index = usize::from(square.flip_vertical());
 
-        score += piece_square_table[square_index];
+        term(offset + square_index, sign);
     }
-
-    score
 }
@@ -0,0 +1,101 @@
+use shakmaty::{Color, Move, Piece, Square};
+
+const MAX_HISTORY: i32 = 16384;
+
+/// A moved piece and its target square, as recorded in the search stack
+pub type MovedPiece = Option<(Piece, Square)>;
+
+/// Move ordering statistics gathered from beta cutoffs during a search
+pub struct History {
+    // [color][from][to]
+    main: Vec<i16>,
+    // [previous piece][previous to][piece][to], for the moves one and two plies ago
+    continuation: Vec<i16>,
+    // [piece][to][captured role]
+    capture: Vec<i16>,
+}
+
+impl History {
+    pub fn new() -> History {
+        History {
+            main: vec![0; 2 * 64 * 64],
+            continuation: vec![0; 12 * 64 * 12 * 64],
+            capture: vec![0; 12 * 64 * 6],
+        }
+    }
+
+    /// Ordering score of a quiet move from the main and continuation histories
+    pub fn quiet_score(&self, m: &Move, turn: Color, previous: &[MovedPiece; 2]) -> i32 {
+        let mut score = self.main[main_index(m, turn)] as i32;
+
+        for &(previous_piece, previous_to) in previous.iter().flatten() {
+            score +=
+                self.continuation[continuation_index(previous_piece, previous_to, m, turn)] as i32;
+        }
+
+        score
+    }
+
+    pub fn capture_score(&self, m: &Move, turn: Color) -> i32 {
+        self.capture[capture_index(m, turn)] as i32
+    }
+
+    pub fn update_quiet(&mut self, m: &Move, turn: Color, previous: &[MovedPiece; 2], bonus: i32) {
+        update(&mut self.main[main_index(m, turn)], bonus);
+
+        for &(previous_piece, previous_to) in previous.iter().flatten() {
+            update(
+                &mut self.continuation[continuation_index(previous_piece, previous_to, m, turn)],
+                bonus,
+            );
+        }
+    }
+
+    pub fn update_capture(&mut self, m: &Move, turn: Color, bonus: i32) {
+        update(&mut self.capture[capture_index(m, turn)], bonus);
+    }
+}
+
+/// History bonus for the move causing a cutoff, and the penalty for those searched before it
+pub fn bonus(depth: i16) -> i32 {
+    (depth as i32 * depth as i32 * 16).min(1600)
+}
+
+// Moves the entry towards the bonus, the closer it already is to the limit the smaller the step
+fn update(entry: &mut i16, bonus: i32) {
+    let value = *entry as i32;
+
+    *entry = (value + bonus - value * bonus.abs() / MAX_HISTORY) as i16;
+}
+
+fn piece_index(piece: Piece) -> usize {
+    usize::from(piece.color.is_white()) * 6 + piece.role as usize - 1
+}
+
+fn main_index(m: &Move, turn: Color) -> usize {
+    // Drops have no origin, they use their target square instead
+    let from = m.from().unwrap_or(m.to());
+
+    usize::from(turn.is_white()) * 64 * 64 + from as usize * 64 + m.to() as usize
+}
+
+fn continuation_index(previous_piece: Piece, previous_to: Square, m: &Move, turn: Color) -> usize {
+    let piece = Piece {
+        color: turn,
+        role: m.role(),
+    };
+
+    ((piece_index(previous_piece) * 64 + previous_to as usize) * 12 + piece_index(piece)) * 64
+        + m.to() as usize
+}
+
+fn capture_index(m: &Move, turn: Color) -> usize {
+    let piece = Piece {
+        color: turn,
+        role: m.role(),
+    };
+
+    let captured = m.capture().map_or(0, |role| role as usize - 1);
+
+    (piece_index(piece) * 64 + m.to() as usize) * 6 + captured
+}
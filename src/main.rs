@@ -1,12 +1,45 @@
 use engine::Engine;
 
+mod analyze;
+mod arena;
+mod book;
+mod correction;
+mod datagen;
+mod endgame;
 mod engine;
+mod epd;
+mod epdtest;
 mod evaluate;
+mod history;
+mod nnue;
+mod params;
+mod pgn;
+mod random;
+mod repetition;
 mod search;
+mod see;
+mod skill;
+mod sprt;
+mod stats;
+mod tablebase;
 mod timer;
+mod tune;
 mod uci;
+mod variants;
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    match args.get(1).map(String::as_str) {
+        Some("tune") => return tune::run(&args[2..]),
+        Some("datagen") => return datagen::run(&args[2..]),
+        Some("book") => return book::run(&args[2..]),
+        Some("epdtest") => return epdtest::run(&args[2..]),
+        Some("match") => return arena::run(&args[2..]),
+        Some("analyze") => return analyze::run(&args[2..]),
+        _ => {}
+    }
+
     println!("Reggz UCI Chess engine by tailow");
 
     let mut engine: Engine = Engine::new();
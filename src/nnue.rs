@@ -0,0 +1,425 @@
+use shakmaty::{Board, Color, Move, Piece, Role, Square};
+use std::fs;
+use std::sync::Arc;
+
+const INPUT_SIZE: usize = 768;
+
+// Quantisation of the feature transformer and the output layer
+const QA: i32 = 255;
+const QB: i32 = 64;
+const SCALE: i32 = 400;
+
+#[cfg(feature = "embedded-nnue")]
+static EMBEDDED_NETWORK: &[u8] = include_bytes!(env!("REGGZ_EVALFILE"));
+
+/// A (768 -> N)x2 -> 1 perspective network with int16 quantised weights.
+///
+/// The file layout is little-endian i16 throughout: feature weights [768][N],
+/// feature biases [N], output weights [2N] (side to move first) and the output bias,
+/// optionally followed by padding.
+pub struct Network {
+    hidden_size: usize,
+    feature_weights: Vec<i16>,
+    feature_biases: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i16,
+}
+
+impl Network {
+    pub fn load(path: &str) -> Result<Network, String> {
+        let bytes = fs::read(path).map_err(|error| format!("{path}: {error}"))?;
+
+        Network::from_bytes(&bytes)
+    }
+
+    #[cfg(feature = "embedded-nnue")]
+    pub fn embedded() -> Option<Network> {
+        Network::from_bytes(EMBEDDED_NETWORK).ok()
+    }
+
+    #[cfg(not(feature = "embedded-nnue"))]
+    pub fn embedded() -> Option<Network> {
+        None
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Network, String> {
+        let values: Vec<i16> = bytes
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+            .collect();
+
+        // 768N + N + 2N + 1 values, padding may follow
+        let hidden_size = values.len().saturating_sub(1) / (INPUT_SIZE + 3);
+
+        if hidden_size == 0 || !hidden_size.is_multiple_of(16) {
+            return Err(format!("invalid network size of {} bytes", bytes.len()));
+        }
+
+        let mut values = values.into_iter();
+
+        let feature_weights: Vec<i16> = values.by_ref().take(INPUT_SIZE * hidden_size).collect();
+        let feature_biases: Vec<i16> = values.by_ref().take(hidden_size).collect();
+        let output_weights: Vec<i16> = values.by_ref().take(2 * hidden_size).collect();
+        let output_bias = values.next().unwrap_or(0);
+
+        Ok(Network {
+            hidden_size,
+            feature_weights,
+            feature_biases,
+            output_weights,
+            output_bias,
+        })
+    }
+
+    pub fn hidden_size(&self) -> usize {
+        self.hidden_size
+    }
+
+    fn feature_weights(&self, feature: usize) -> &[i16] {
+        &self.feature_weights[feature * self.hidden_size..(feature + 1) * self.hidden_size]
+    }
+}
+
+#[derive(Clone)]
+struct Accumulator {
+    white: Vec<i16>,
+    black: Vec<i16>,
+}
+
+impl Accumulator {
+    fn perspective_mut(&mut self, color: Color) -> &mut Vec<i16> {
+        match color {
+            Color::White => &mut self.white,
+            Color::Black => &mut self.black,
+        }
+    }
+}
+
+/// Accumulators for every ply of the search, so unmaking a move is free
+pub struct AccumulatorStack {
+    network: Arc<Network>,
+    accumulators: Vec<Accumulator>,
+}
+
+impl AccumulatorStack {
+    pub fn new(network: Arc<Network>) -> AccumulatorStack {
+        AccumulatorStack {
+            network,
+            accumulators: Vec::with_capacity(256),
+        }
+    }
+
+    /// Computes the accumulator at the given ply from scratch
+    pub fn refresh(&mut self, board: &Board, ply: u16) {
+        self.ensure_ply(ply as usize);
+
+        let network = &self.network;
+        let accumulator = &mut self.accumulators[ply as usize];
+
+        for color in Color::ALL {
+            let values = accumulator.perspective_mut(color);
+
+            values.copy_from_slice(&network.feature_biases);
+
+            for (square, piece) in board {
+                add(
+                    values,
+                    network.feature_weights(feature(color, piece, square)),
+                );
+            }
+        }
+    }
+
+    /// Derives the accumulator at ply + 1 from the one at ply and the move played
+    pub fn make_move(&mut self, us: Color, legal_move: Move, ply: u16) {
+        let ply = ply as usize;
+
+        self.ensure_ply(ply + 1);
+
+        let (parents, children) = self.accumulators.split_at_mut(ply + 1);
+        let parent = &parents[ply];
+        let child = &mut children[0];
+
+        let mut added: [Option<(Piece, Square)>; 2] = [None; 2];
+        let mut removed: [Option<(Piece, Square)>; 2] = [None; 2];
+
+        match legal_move {
+            Move::Normal {
+                role,
+                from,
+                capture,
+                to,
+                promotion,
+            } => {
+                removed[0] = Some((role.of(us), from));
+                added[0] = Some((promotion.unwrap_or(role).of(us), to));
+
+                if let Some(captured) = capture {
+                    removed[1] = Some((captured.of(!us), to));
+                }
+            }
+            Move::EnPassant { from, to } => {
+                removed[0] = Some((Role::Pawn.of(us), from));
+                added[0] = Some((Role::Pawn.of(us), to));
+                removed[1] = Some((
+                    Role::Pawn.of(!us),
+                    Square::from_coords(to.file(), from.rank()),
+                ));
+            }
+            Move::Castle { king, rook } => {
+                let side = legal_move.castling_side().unwrap();
+
+                removed[0] = Some((Role::King.of(us), king));
+                removed[1] = Some((Role::Rook.of(us), rook));
+                added[0] = Some((Role::King.of(us), side.king_to(us)));
+                added[1] = Some((Role::Rook.of(us), side.rook_to(us)));
+            }
+            Move::Put { role, to } => {
+                added[0] = Some((role.of(us), to));
+            }
+        }
+
+        for color in Color::ALL {
+            let values = child.perspective_mut(color);
+
+            values.copy_from_slice(match color {
+                Color::White => &parent.white,
+                Color::Black => &parent.black,
+            });
+
+            for (piece, square) in added.iter().flatten() {
+                add(
+                    values,
+                    self.network
+                        .feature_weights(feature(color, *piece, *square)),
+                );
+            }
+
+            for (piece, square) in removed.iter().flatten() {
+                sub(
+                    values,
+                    self.network
+                        .feature_weights(feature(color, *piece, *square)),
+                );
+            }
+        }
+    }
+
+    /// Evaluates the position at the given ply from the side to move's perspective
+    pub fn evaluate(&self, ply: u16, turn: Color) -> i16 {
+        let accumulator = &self.accumulators[ply as usize];
+
+        let (us, them) = match turn {
+            Color::White => (&accumulator.white, &accumulator.black),
+            Color::Black => (&accumulator.black, &accumulator.white),
+        };
+
+        let hidden_size = self.network.hidden_size;
+
+        let output = crelu_dot(us, &self.network.output_weights[..hidden_size])
+            + crelu_dot(them, &self.network.output_weights[hidden_size..])
+            + self.network.output_bias as i32;
+
+        (output as i64 * SCALE as i64 / (QA * QB) as i64).clamp(-20000, 20000) as i16
+    }
+
+    fn ensure_ply(&mut self, ply: usize) {
+        while self.accumulators.len() <= ply {
+            self.accumulators.push(Accumulator {
+                white: vec![0; self.network.hidden_size],
+                black: vec![0; self.network.hidden_size],
+            });
+        }
+    }
+}
+
+fn feature(perspective: Color, piece: Piece, square: Square) -> usize {
+    let (own, square) = match perspective {
+        Color::White => (piece.color == Color::White, square),
+        Color::Black => (piece.color == Color::Black, square.flip_vertical()),
+    };
+
+    let color_offset = if own { 0 } else { 384 };
+
+    color_offset + (piece.role as usize - 1) * 64 + usize::from(square)
+}
+
+fn add(values: &mut [i16], weights: &[i16]) {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        // Safety: AVX2 support was checked at runtime
+        unsafe { avx2::add(values, weights) };
+
+        return;
+    }
+
+    for (value, weight) in values.iter_mut().zip(weights) {
+        *value = value.wrapping_add(*weight);
+    }
+}
+
+fn sub(values: &mut [i16], weights: &[i16]) {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        // Safety: AVX2 support was checked at runtime
+        unsafe { avx2::sub(values, weights) };
+
+        return;
+    }
+
+    for (value, weight) in values.iter_mut().zip(weights) {
+        *value = value.wrapping_sub(*weight);
+    }
+}
+
+fn crelu_dot(values: &[i16], weights: &[i16]) -> i32 {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        // Safety: AVX2 support was checked at runtime
+        return unsafe { avx2::crelu_dot(values, weights) };
+    }
+
+    values
+        .iter()
+        .zip(weights)
+        .map(|(value, weight)| (*value as i32).clamp(0, QA) * *weight as i32)
+        .sum()
+}
+
+// The hidden size is always a multiple of 16, so every slice splits into whole registers
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use super::QA;
+    use std::arch::x86_64::*;
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn add(values: &mut [i16], weights: &[i16]) {
+        for (value, weight) in values.chunks_exact_mut(16).zip(weights.chunks_exact(16)) {
+            let v = _mm256_loadu_si256(value.as_ptr() as *const __m256i);
+            let w = _mm256_loadu_si256(weight.as_ptr() as *const __m256i);
+
+            _mm256_storeu_si256(value.as_mut_ptr() as *mut __m256i, _mm256_add_epi16(v, w));
+        }
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn sub(values: &mut [i16], weights: &[i16]) {
+        for (value, weight) in values.chunks_exact_mut(16).zip(weights.chunks_exact(16)) {
+            let v = _mm256_loadu_si256(value.as_ptr() as *const __m256i);
+            let w = _mm256_loadu_si256(weight.as_ptr() as *const __m256i);
+
+            _mm256_storeu_si256(value.as_mut_ptr() as *mut __m256i, _mm256_sub_epi16(v, w));
+        }
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn crelu_dot(values: &[i16], weights: &[i16]) -> i32 {
+        let zero = _mm256_setzero_si256();
+        let max = _mm256_set1_epi16(QA as i16);
+
+        let mut sum = _mm256_setzero_si256();
+
+        for (value, weight) in values.chunks_exact(16).zip(weights.chunks_exact(16)) {
+            let v = _mm256_loadu_si256(value.as_ptr() as *const __m256i);
+            let w = _mm256_loadu_si256(weight.as_ptr() as *const __m256i);
+
+            let clipped = _mm256_min_epi16(_mm256_max_epi16(v, zero), max);
+
+            sum = _mm256_add_epi32(sum, _mm256_madd_epi16(clipped, w));
+        }
+
+        let high = _mm256_extracti128_si256(sum, 1);
+        let low = _mm256_castsi256_si128(sum);
+        let sum = _mm_add_epi32(high, low);
+        let sum = _mm_add_epi32(sum, _mm_shuffle_epi32(sum, 0b01_00_11_10));
+        let sum = _mm_add_epi32(sum, _mm_shuffle_epi32(sum, 0b10_11_00_01));
+
+        _mm_cvtsi128_si32(sum)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Rng;
+    use shakmaty::fen::Fen;
+    use shakmaty::{CastlingMode, Chess, Position};
+
+    fn random_network(rng: &mut Rng) -> Arc<Network> {
+        let hidden_size = 32;
+
+        let bytes: Vec<u8> = (0..(INPUT_SIZE + 3) * hidden_size + 1)
+            .flat_map(|_| (rng.below(129) as i16 - 64).to_le_bytes())
+            .collect();
+
+        Arc::new(Network::from_bytes(&bytes).unwrap())
+    }
+
+    // Random games from positions full of captures, castling, en passant and promotions,
+    // with the incrementally updated accumulator compared to a full refresh after every move
+    #[test]
+    fn incremental_updates_match_refresh() {
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+        ];
+
+        let mut rng = Rng::new(1);
+
+        let network = random_network(&mut rng);
+
+        let mut incremental = AccumulatorStack::new(network.clone());
+        let mut refreshed = AccumulatorStack::new(network);
+
+        let (mut captures, mut castles, mut en_passants, mut promotions) = (0, 0, 0, 0);
+
+        for fen in fens {
+            for _ in 0..100 {
+                let mut board: Chess = Fen::from_ascii(fen.as_bytes())
+                    .unwrap()
+                    .into_position(CastlingMode::Standard)
+                    .unwrap();
+
+                incremental.refresh(board.board(), 0);
+
+                for ply in 0..40 {
+                    let legal_moves = board.legal_moves();
+
+                    if legal_moves.is_empty() {
+                        break;
+                    }
+
+                    let legal_move = legal_moves[rng.below(legal_moves.len() as u64) as usize];
+
+                    captures += usize::from(legal_move.is_capture());
+                    castles += usize::from(legal_move.is_castle());
+                    en_passants += usize::from(legal_move.is_en_passant());
+                    promotions += usize::from(legal_move.is_promotion());
+
+                    incremental.make_move(board.turn(), legal_move, ply);
+                    board.play_unchecked(legal_move);
+                    refreshed.refresh(board.board(), ply + 1);
+
+                    let (a, b) = (
+                        &incremental.accumulators[ply as usize + 1],
+                        &refreshed.accumulators[ply as usize + 1],
+                    );
+
+                    assert!(
+                        a.white == b.white && a.black == b.black,
+                        "{fen} after {}",
+                        legal_move.to_uci(CastlingMode::Standard)
+                    );
+                    assert_eq!(
+                        incremental.evaluate(ply + 1, board.turn()),
+                        refreshed.evaluate(ply + 1, board.turn())
+                    );
+                }
+            }
+        }
+
+        assert!(captures > 0 && castles > 0 && en_passants > 0 && promotions > 0);
+    }
+}
@@ -0,0 +1,214 @@
+use shakmaty::Role;
+
+/// What the search does at nodes without a hash move to order first
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InternalIterative {
+    None,
+    // Reduce the depth by one
+    Reduction,
+    // Run a shallower search to find a hash move
+    Deepening,
+}
+
+impl InternalIterative {
+    pub const ALL: [InternalIterative; 3] = [
+        InternalIterative::None,
+        InternalIterative::Reduction,
+        InternalIterative::Deepening,
+    ];
+
+    /// Value of the UCI combo option
+    pub fn name(self) -> &'static str {
+        match self {
+            InternalIterative::None => "None",
+            InternalIterative::Reduction => "Reduction",
+            InternalIterative::Deepening => "Deepening",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<InternalIterative, String> {
+        InternalIterative::ALL
+            .into_iter()
+            .find(|mode| mode.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("invalid value {name} for InternalIterative"))
+    }
+}
+
+/// Constants of the search that can be changed at runtime for tuning
+#[derive(Clone)]
+pub struct SearchParams {
+    pub aspiration_window: i16,
+    pub aspiration_widening: i16,
+    pub internal_iterative: InternalIterative,
+    pub internal_iterative_depth: i16,
+    pub razoring_depth: i16,
+    pub razoring_margin: i16,
+    pub probcut_depth: i16,
+    pub probcut_margin: i16,
+    pub probcut_reduction: i16,
+    // Margin over the captured piece for delta pruning in quiescence
+    pub delta_margin: i16,
+    // Move ordering values of pawn, knight, bishop, rook and queen
+    pub mvv_lva: [i16; 5],
+}
+
+impl Default for SearchParams {
+    fn default() -> SearchParams {
+        SearchParams {
+            aspiration_window: 50,
+            aspiration_widening: 100,
+            // Level with deepening over a 1000-game match, see the README
+            internal_iterative: InternalIterative::Reduction,
+            internal_iterative_depth: 4,
+            razoring_depth: 2,
+            razoring_margin: 300,
+            probcut_depth: 5,
+            probcut_margin: 200,
+            probcut_reduction: 4,
+            delta_margin: 200,
+            mvv_lva: [100, 300, 350, 500, 900],
+        }
+    }
+}
+
+impl SearchParams {
+    /// Move ordering value of a piece, kings are ordered after every other attacker
+    pub fn mvv_lva(&self, role: Role) -> i16 {
+        match role {
+            Role::King => 2000,
+            _ => self.mvv_lva[role as usize - 1],
+        }
+    }
+
+    pub fn set(&mut self, name: &str, value: i16) -> Result<(), String> {
+        let tunable = TUNABLES
+            .iter()
+            .find(|t| t.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("unknown parameter {name}"))?;
+
+        if !(tunable.min..=tunable.max).contains(&value) {
+            return Err(format!(
+                "{} must be between {} and {}",
+                tunable.name, tunable.min, tunable.max
+            ));
+        }
+
+        *(tunable.value)(self) = value;
+
+        Ok(())
+    }
+}
+
+/// A parameter exposed as a UCI spin option when built with the tune feature
+pub struct Tunable {
+    pub name: &'static str,
+    pub min: i16,
+    pub max: i16,
+    pub value: fn(&mut SearchParams) -> &mut i16,
+}
+
+pub static TUNABLES: [Tunable; 14] = [
+    Tunable {
+        name: "AspirationWindow",
+        min: 10,
+        max: 200,
+        value: |p| &mut p.aspiration_window,
+    },
+    Tunable {
+        name: "AspirationWidening",
+        min: 20,
+        max: 400,
+        value: |p| &mut p.aspiration_widening,
+    },
+    Tunable {
+        name: "InternalIterativeDepth",
+        min: 2,
+        max: 10,
+        value: |p| &mut p.internal_iterative_depth,
+    },
+    Tunable {
+        name: "RazoringDepth",
+        min: 0,
+        max: 4,
+        value: |p| &mut p.razoring_depth,
+    },
+    Tunable {
+        name: "RazoringMargin",
+        min: 100,
+        max: 600,
+        value: |p| &mut p.razoring_margin,
+    },
+    Tunable {
+        name: "ProbCutDepth",
+        min: 3,
+        max: 10,
+        value: |p| &mut p.probcut_depth,
+    },
+    Tunable {
+        name: "ProbCutMargin",
+        min: 50,
+        max: 400,
+        value: |p| &mut p.probcut_margin,
+    },
+    Tunable {
+        name: "ProbCutReduction",
+        min: 2,
+        max: 6,
+        value: |p| &mut p.probcut_reduction,
+    },
+    Tunable {
+        name: "DeltaMargin",
+        min: 0,
+        max: 600,
+        value: |p| &mut p.delta_margin,
+    },
+    Tunable {
+        name: "MvvLvaPawn",
+        min: 50,
+        max: 200,
+        value: |p| &mut p.mvv_lva[0],
+    },
+    Tunable {
+        name: "MvvLvaKnight",
+        min: 150,
+        max: 500,
+        value: |p| &mut p.mvv_lva[1],
+    },
+    Tunable {
+        name: "MvvLvaBishop",
+        min: 150,
+        max: 500,
+        value: |p| &mut p.mvv_lva[2],
+    },
+    Tunable {
+        name: "MvvLvaRook",
+        min: 300,
+        max: 800,
+        value: |p| &mut p.mvv_lva[3],
+    },
+    Tunable {
+        name: "MvvLvaQueen",
+        min: 600,
+        max: 1200,
+        value: |p| &mut p.mvv_lva[4],
+    },
+];
+
+/// Parameters in the SPSA tuner format: name, int, value, min, max, step, learning rate
+#[cfg(feature = "tune")]
+pub fn spsa_text(params: &SearchParams) -> String {
+    let mut params = params.clone();
+
+    TUNABLES
+        .iter()
+        .map(|tunable| {
+            let value = *(tunable.value)(&mut params);
+            let step = ((tunable.max - tunable.min) as f64 / 20.0).max(0.5);
+
+            format!(
+                "{}, int, {value}, {}, {}, {step}, 0.002\n",
+                tunable.name, tunable.min, tunable.max
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,207 @@
+use shakmaty::fen::Fen;
+use shakmaty::san::SanPlus;
+use shakmaty::{CastlingMode, Chess, Color, Move, Position};
+use std::mem;
+
+/// A game from a PGN file, comments and variations are skipped
+#[derive(Clone)]
+pub struct Game {
+    pub headers: Vec<(String, String)>,
+    pub moves: Vec<String>,
+    pub result: String,
+}
+
+impl Game {
+    fn empty() -> Game {
+        Game {
+            headers: Vec::new(),
+            moves: Vec::new(),
+            result: String::from("*"),
+        }
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn starting_position(&self) -> Option<Chess> {
+        match self.header("FEN") {
+            Some(fen) => Fen::from_ascii(fen.as_bytes())
+                .ok()?
+                .into_position(CastlingMode::Standard)
+                .ok(),
+            None => Some(Chess::new()),
+        }
+    }
+
+    /// The starting position and the moves of the game, up to the first illegal move
+    pub fn play(&self) -> Option<(Chess, Vec<Move>)> {
+        let start = self.starting_position()?;
+
+        let mut board = start.clone();
+        let mut moves: Vec<Move> = Vec::with_capacity(self.moves.len());
+
+        for san in &self.moves {
+            let Some(legal_move) = SanPlus::from_ascii(san.as_bytes())
+                .ok()
+                .and_then(|san_plus| san_plus.san.to_move(&board).ok())
+            else {
+                break;
+            };
+
+            board.play_unchecked(legal_move);
+
+            moves.push(legal_move);
+        }
+
+        Some((start, moves))
+    }
+
+    /// Writes the game as PGN text, with the movetext wrapped at 80 columns
+    pub fn to_pgn(&self) -> String {
+        self.to_annotated_pgn(&[])
+    }
+
+    /// Writes the game as PGN text with each annotation, such as NAGs, comments and
+    /// variations, following the move of the same index
+    pub fn to_annotated_pgn(&self, annotations: &[String]) -> String {
+        let mut text = String::new();
+
+        for (name, value) in &self.headers {
+            text.push_str(&format!("[{name} \"{value}\"]\n"));
+        }
+
+        text.push('\n');
+
+        let start = self.starting_position().unwrap_or_default();
+
+        let mut fullmoves = start.fullmoves().get();
+        let mut turn = start.turn();
+
+        let mut tokens: Vec<String> = Vec::with_capacity(self.moves.len() * 2 + 1);
+
+        for (i, san) in self.moves.iter().enumerate() {
+            // Black moves are numbered again after an annotation
+            let annotated = i > 0 && annotations.get(i - 1).is_some_and(|a| !a.is_empty());
+
+            match turn {
+                Color::White => tokens.push(format!("{fullmoves}.")),
+                Color::Black if i == 0 || annotated => tokens.push(format!("{fullmoves}...")),
+                Color::Black => {}
+            }
+
+            tokens.push(san.clone());
+
+            if let Some(annotation) = annotations.get(i) {
+                tokens.extend(annotation.split_whitespace().map(str::to_string));
+            }
+
+            if turn == Color::Black {
+                fullmoves += 1;
+            }
+
+            turn = !turn;
+        }
+
+        tokens.push(self.result.clone());
+
+        let mut line_length = 0;
+
+        for token in tokens {
+            if line_length > 0 && line_length + token.len() + 1 > 80 {
+                text.push('\n');
+                line_length = 0;
+            } else if line_length > 0 {
+                text.push(' ');
+                line_length += 1;
+            }
+
+            line_length += token.len();
+            text.push_str(&token);
+        }
+
+        text.push_str("\n\n");
+
+        text
+    }
+}
+
+/// Reads every game from PGN text
+pub fn read_games(text: &str) -> Vec<Game> {
+    let mut games: Vec<Game> = Vec::new();
+
+    let mut game = Game::empty();
+
+    let mut chars = text.chars().peekable();
+
+    let mut variation_depth: u32 = 0;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '[' if variation_depth == 0 => {
+                // A header after movetext starts a new game
+                if !game.moves.is_empty() {
+                    games.push(mem::replace(&mut game, Game::empty()));
+                }
+
+                let header: String = chars.by_ref().take_while(|c| *c != ']').collect();
+
+                if let Some((name, value)) = header.trim().split_once(' ') {
+                    game.headers
+                        .push((name.to_string(), value.trim().trim_matches('"').to_string()));
+                }
+            }
+            '{' => {
+                chars.by_ref().find(|c| *c == '}');
+            }
+            ';' => {
+                chars.by_ref().find(|c| *c == '\n');
+            }
+            '(' => variation_depth += 1,
+            ')' => variation_depth = variation_depth.saturating_sub(1),
+            c if c.is_whitespace() => {}
+            c => {
+                let mut token = String::from(c);
+
+                while let Some(next) = chars.peek() {
+                    if next.is_whitespace() || "{}();[".contains(*next) {
+                        break;
+                    }
+
+                    token.push(chars.next().unwrap());
+                }
+
+                if variation_depth > 0 || token.starts_with('$') {
+                    continue;
+                }
+
+                match token.as_str() {
+                    "1-0" | "0-1" | "1/2-1/2" | "*" => {
+                        game.result = token;
+
+                        games.push(mem::replace(&mut game, Game::empty()));
+                    }
+                    _ => {
+                        // Strip move numbers such as "12." or "12..."
+                        let san =
+                            token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+
+                        if !san.is_empty() {
+                            game.moves
+                                .push(san.trim_end_matches(['!', '?']).to_string());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if !game.moves.is_empty() {
+        games.push(game);
+    }
+
+    games
+}
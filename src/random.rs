@@ -0,0 +1,36 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Small xorshift64* generator, good enough for openings and move selection
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // The state must never be zero
+        Rng(seed ^ 0x9E37_79B9_7F4A_7C15 | 1)
+    }
+
+    pub fn from_time(salt: u64) -> Rng {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+
+        Rng::new(nanos.wrapping_add(salt.wrapping_mul(0x2545_F491_4F6C_DD1D)))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform value in 0..bound
+    pub fn below(&mut self, bound: u64) -> u64 {
+        if bound == 0 {
+            return 0;
+        }
+
+        self.next_u64() % bound
+    }
+}
@@ -0,0 +1,251 @@
+use shakmaty::attacks::{attacks, between};
+use shakmaty::zobrist::{Zobrist64, ZobristValue};
+use shakmaty::{Bitboard, Board, Color, Piece, Role, Square};
+use std::sync::OnceLock;
+
+const CUCKOO_SIZE: usize = 8192;
+
+/// Whether the current position, the last one in the history, is drawn by repetition.
+///
+/// Only positions since the last irreversible move can repeat. A single repetition
+/// inside the search tree is enough, positions from the game need a threefold repetition.
+pub fn is_repetition(position_history: &[Zobrist64], halfmoves: u32, ply: u16) -> bool {
+    let Some((&hash, previous)) = position_history.split_last() else {
+        return false;
+    };
+
+    let end = (halfmoves as usize).min(previous.len());
+
+    let mut repetitions = 0;
+
+    // The same side is to move every other ply, and a position can't repeat within two
+    for distance in (4..=end).step_by(2) {
+        if previous[previous.len() - distance] == hash {
+            if distance < ply as usize {
+                return true;
+            }
+
+            repetitions += 1;
+
+            if repetitions >= 2 {
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+/// Whether the side to move has a reversible move reaching a position from the history,
+/// found through the cuckoo table of the hash differences of every such move
+pub fn has_upcoming_repetition(
+    board: &Board,
+    turn: Color,
+    position_history: &[Zobrist64],
+    halfmoves: u32,
+    ply: u16,
+) -> bool {
+    let Some((&hash, previous)) = position_history.split_last() else {
+        return false;
+    };
+
+    let end = (halfmoves as usize).min(previous.len());
+
+    let cuckoo = cuckoo();
+
+    for distance in (3..=end).step_by(2) {
+        let index = previous.len() - distance;
+
+        let move_key = hash.0 ^ previous[index].0;
+
+        let slot = if cuckoo.keys[h1(move_key)] == move_key {
+            h1(move_key)
+        } else if cuckoo.keys[h2(move_key)] == move_key {
+            h2(move_key)
+        } else {
+            continue;
+        };
+
+        let (from, to) = cuckoo.moves[slot];
+
+        if (between(from, to) & board.occupied()).any() {
+            continue;
+        }
+
+        // Both directions share a slot, the piece is on whichever square is occupied
+        let square = if board.occupied().contains(from) {
+            from
+        } else {
+            to
+        };
+
+        if board.color_at(square) != Some(turn) {
+            continue;
+        }
+
+        // Before the root, the position reached has to have repeated already
+        if distance < ply as usize || previous[..index].contains(&previous[index]) {
+            return true;
+        }
+    }
+
+    false
+}
+
+struct Cuckoo {
+    keys: Vec<u64>,
+    moves: Vec<(Square, Square)>,
+}
+
+fn h1(key: u64) -> usize {
+    (key & 0x1fff) as usize
+}
+
+fn h2(key: u64) -> usize {
+    ((key >> 16) & 0x1fff) as usize
+}
+
+// Every move of a non-pawn piece between two squares on an empty board, keyed by the
+// hash difference it makes including the side to move
+fn cuckoo() -> &'static Cuckoo {
+    static CUCKOO: OnceLock<Cuckoo> = OnceLock::new();
+
+    CUCKOO.get_or_init(|| {
+        let mut cuckoo = Cuckoo {
+            keys: vec![0; CUCKOO_SIZE],
+            moves: vec![(Square::A1, Square::A1); CUCKOO_SIZE],
+        };
+
+        for color in Color::ALL {
+            for role in [
+                Role::Knight,
+                Role::Bishop,
+                Role::Rook,
+                Role::Queen,
+                Role::King,
+            ] {
+                let piece = Piece { color, role };
+
+                for from in Square::ALL {
+                    for to in attacks(from, piece, Bitboard::EMPTY) {
+                        if to <= from {
+                            continue;
+                        }
+
+                        let mut key = Zobrist64::zobrist_for_piece(from, piece).0
+                            ^ Zobrist64::zobrist_for_piece(to, piece).0
+                            ^ Zobrist64::zobrist_for_white_turn().0;
+                        let mut entry = (from, to);
+
+                        let mut slot = h1(key);
+
+                        // Insert, displacing entries to their other slot until one is empty
+                        loop {
+                            std::mem::swap(&mut cuckoo.keys[slot], &mut key);
+                            std::mem::swap(&mut cuckoo.moves[slot], &mut entry);
+
+                            if key == 0 {
+                                break;
+                            }
+
+                            slot = if slot == h1(key) { h2(key) } else { h1(key) };
+                        }
+                    }
+                }
+            }
+        }
+
+        cuckoo
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::uci::UciMove;
+    use shakmaty::{Chess, EnPassantMode, Position};
+
+    // Plays the moves from the start position and returns the final position with the
+    // hashes of every position on the way
+    fn play(moves: &str) -> (Chess, Vec<Zobrist64>) {
+        let mut board = Chess::default();
+        let mut position_history = vec![board.zobrist_hash(EnPassantMode::Legal)];
+
+        for uci in moves.split_whitespace() {
+            let legal_move = uci.parse::<UciMove>().unwrap().to_move(&board).unwrap();
+
+            board.play_unchecked(legal_move);
+            position_history.push(board.zobrist_hash(EnPassantMode::Legal));
+        }
+
+        (board, position_history)
+    }
+
+    #[test]
+    fn threefold_repetition() {
+        let (board, position_history) = play("g1f3 g8f6 f3g1 f6g8");
+
+        assert!(!is_repetition(&position_history, board.halfmoves(), 0));
+
+        // A single repetition is enough inside the search tree
+        assert!(is_repetition(&position_history, board.halfmoves(), 5));
+
+        let (board, position_history) = play("g1f3 g8f6 f3g1 f6g8 g1f3 g8f6 f3g1 f6g8");
+
+        assert!(is_repetition(&position_history, board.halfmoves(), 0));
+    }
+
+    #[test]
+    fn repetition_cut_off_by_pawn_move() {
+        let (board, position_history) = play("e2e4 e7e5 g1f3 g8f6 f3g1 f6g8 g1f3 g8f6 f3g1 f6g8");
+
+        assert_eq!(board.halfmoves(), 8);
+        assert!(is_repetition(&position_history, board.halfmoves(), 0));
+
+        // Had the first knight move been a pawn move, the oldest occurrence would be out of reach
+        assert!(!is_repetition(&position_history, 7, 0));
+    }
+
+    #[test]
+    fn upcoming_repetition() {
+        // Black can return to the start position with f6g8, a move before it repeats
+        let (board, position_history) = play("g1f3 g8f6 f3g1");
+
+        assert!(!is_repetition(&position_history, board.halfmoves(), 4));
+        assert!(has_upcoming_repetition(
+            board.board(),
+            board.turn(),
+            &position_history,
+            board.halfmoves(),
+            4
+        ));
+
+        // Not when the clock was reset after the start position, as by a pawn move
+        assert!(!has_upcoming_repetition(
+            board.board(),
+            board.turn(),
+            &position_history,
+            2,
+            4
+        ));
+
+        // At the root the start position has to have repeated already
+        assert!(!has_upcoming_repetition(
+            board.board(),
+            board.turn(),
+            &position_history,
+            board.halfmoves(),
+            0
+        ));
+
+        let (board, position_history) = play("g1f3 g8f6 f3g1 f6g8 g1f3 g8f6 f3g1");
+
+        assert!(has_upcoming_repetition(
+            board.board(),
+            board.turn(),
+            &position_history,
+            board.halfmoves(),
+            0
+        ));
+    }
+}
@@ -1,7 +1,19 @@
-use crate::engine::TRANSPOSITION_TABLE_LENGTH;
-use crate::evaluate::evaluate;
+use crate::correction::CorrectionHistory;
+use crate::endgame;
+use crate::evaluate::material_value;
+use crate::history::{self, History, MovedPiece};
+use crate::nnue::AccumulatorStack;
+use crate::params::{InternalIterative, SearchParams};
+use crate::repetition::{has_upcoming_repetition, is_repetition};
+use crate::see::see;
+use crate::stats::{self, SearchStats, Stage};
+use crate::tablebase::{Tablebase, Wdl};
+use crate::variants::SearchPosition;
 use shakmaty::zobrist::Zobrist64;
-use shakmaty::{CastlingMode, Chess, Color, EnPassantMode, Move, MoveList, Position, Role};
+use shakmaty::{
+    CastlingMode, Chess, Color, EnPassantMode, KnownOutcome, Move, MoveList, Outcome, Piece,
+    Position,
+};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::SystemTime;
@@ -22,31 +34,107 @@ pub struct Node {
     pub node_type: NodeType,
 }
 
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    pub score: i16,
+    pub depth: i16,
+    pub nodes: u64,
+    // Best move and score of each root line of the last complete iteration, best first
+    pub lines: Vec<(Move, i16)>,
+    pub principal_variation: Vec<Move>,
+}
+
 pub struct Searcher {
     pub nodes: u64,
+    // Deepest ply reached in the current iteration
+    pub seldepth: u16,
+    pub start_time: SystemTime,
+    pub max_nodes: Option<u64>,
     pub searching: Arc<AtomicBool>,
     pub _pondering: Arc<AtomicBool>,
     pub max_depth: Option<i16>,
     pub debug: Arc<AtomicBool>,
     pub best_root_move: Option<Move>,
+    pub nnue: Option<AccumulatorStack>,
+    pub tablebase: Option<Arc<Tablebase>>,
+    pub tb_probe_limit: u32,
+    pub tb_hits: u64,
+    // Moves searched at the root, all legal moves when empty
+    pub root_moves: Vec<Move>,
+    // Number of root lines to search, and the moves of the lines already searched
+    pub multi_pv: usize,
+    excluded_root_moves: Vec<Move>,
+    pub castling_mode: CastlingMode,
+    // How much worse than equal a draw is for the root side, none for objective analysis
+    // scores without contempt or jittered repetitions
+    pub contempt: Option<i16>,
+    pub stats: SearchStats,
+    pub params: SearchParams,
+    pub history: History,
+    pub correction: CorrectionHistory,
+    // The move played at each ply of the current line
+    pub stack: Vec<StackEntry>,
+}
+
+#[derive(Clone, Default)]
+pub struct StackEntry {
+    pub current_move: Option<Move>,
+    pub moved_piece: Option<Piece>,
 }
 
 pub const MATE: i16 = 31000;
 pub const MATE_MAX_PLIES: i16 = 128;
 
+// Tablebase wins are scored below any mate
+pub const TB_WIN: i16 = 30000;
+
 impl Searcher {
-    pub fn search(
+    pub fn new(searching: Arc<AtomicBool>, debug: Arc<AtomicBool>) -> Searcher {
+        Searcher {
+            nodes: 0,
+            seldepth: 0,
+            start_time: SystemTime::now(),
+            max_nodes: None,
+            searching,
+            _pondering: Arc::new(AtomicBool::new(false)),
+            max_depth: None,
+            debug,
+            best_root_move: None,
+            nnue: None,
+            tablebase: None,
+            tb_probe_limit: 0,
+            tb_hits: 0,
+            root_moves: Vec::new(),
+            multi_pv: 1,
+            excluded_root_moves: Vec::new(),
+            castling_mode: CastlingMode::Standard,
+            contempt: None,
+            stats: SearchStats::default(),
+            params: SearchParams::default(),
+            history: History::new(),
+            correction: CorrectionHistory::new(),
+            stack: Vec::new(),
+        }
+    }
+
+    pub fn search<P: SearchPosition>(
         &mut self,
-        board: Chess,
+        board: P,
         position_history: &mut Vec<Zobrist64>,
         transposition_table: &mut Arc<Mutex<Vec<Option<Node>>>>,
-    ) {
+    ) -> SearchResult {
         let mut score: Option<i16>;
-        let mut previous_score: Option<i16> = None;
-
+        let mut result = SearchResult {
+            best_move: None,
+            score: 0,
+            depth: 0,
+            nodes: 0,
+            lines: Vec::new(),
+            principal_variation: Vec::new(),
+        };
         let mut best_move: Option<Move> = None;
 
-        let start_time = SystemTime::now();
+        self.start_time = SystemTime::now();
 
         let mut transposition_table = transposition_table.lock().unwrap();
 
@@ -54,189 +142,454 @@ impl Searcher {
 
         let hash = board.zobrist_hash::<Zobrist64>(EnPassantMode::Legal);
 
+        // Aborted searches leave their moves in the history
+        let history_length = position_history.len();
+
+        if let Some(ref mut nnue) = self.nnue {
+            nnue.refresh(board.board(), 0);
+        }
+
+        self.filter_root_moves_by_tablebase(&board);
+
         let mut max_depth: i16 = i16::MAX;
 
         if let Some(custom_max_depth) = self.max_depth {
             max_depth = custom_max_depth;
         }
 
-        for depth in 1..max_depth {
+        let root_move_count = board
+            .legal_moves()
+            .iter()
+            .filter(|m| self.root_moves.is_empty() || self.root_moves.contains(m))
+            .count();
+
+        // Previous iteration's score of each root line, centring its aspiration window
+        let mut previous_scores: Vec<Option<i16>> =
+            vec![None; self.multi_pv.clamp(1, root_move_count.max(1))];
+
+        for depth in 1..=max_depth {
             if !self.searching.load(Ordering::Relaxed) {
                 break;
             }
 
-            let mut lower_window = i16::MIN + 1;
-            let mut upper_window = i16::MAX - 1;
+            self.seldepth = 0;
 
-            if let Some(previous_score) = previous_score {
-                lower_window = previous_score - 50;
-                upper_window = previous_score + 50;
-            }
+            let iteration_start_nodes = self.nodes;
 
-            '_aspiration: loop {
-                let mut alpha = lower_window;
-                let mut beta = upper_window;
+            // Each line excludes the best moves of the lines before it
+            let mut lines: Vec<(Move, i16)> = Vec::new();
+            let mut aborted = false;
 
-                score = self.negamax(
-                    &board,
-                    depth,
-                    0,
-                    &mut alpha,
-                    &mut beta,
-                    if board.turn() == Color::White { 1 } else { -1 },
-                    position_history,
-                    hash,
-                    &mut transposition_table,
-                );
+            self.excluded_root_moves.clear();
+
+            for (pv_index, previous_score) in previous_scores.iter_mut().enumerate() {
+                let mut lower_window = i16::MIN + 1;
+                let mut upper_window = i16::MAX - 1;
+
+                if let Some(previous_score) = *previous_score {
+                    lower_window = previous_score - self.params.aspiration_window;
+                    upper_window = previous_score + self.params.aspiration_window;
+                }
+
+                self.best_root_move = None;
+
+                '_aspiration: loop {
+                    let mut alpha = lower_window;
+                    let mut beta = upper_window;
+
+                    score = self.negamax(
+                        &board,
+                        depth,
+                        0,
+                        &mut alpha,
+                        &mut beta,
+                        if board.turn() == Color::White { 1 } else { -1 },
+                        position_history,
+                        hash,
+                        &mut transposition_table,
+                    );
+
+                    position_history.truncate(history_length);
+
+                    let Some(score) = score else {
+                        break;
+                    };
 
-                if let Some(score) = score {
-                    if score <= lower_window {
-                        lower_window = score - 100;
-                        println!("window miss");
+                    let bound = if score <= lower_window {
+                        lower_window = score - self.params.aspiration_widening;
+
+                        "upperbound"
                     } else if score >= upper_window {
-                        upper_window = score + 100;
-                        println!("window miss");
+                        upper_window = score + self.params.aspiration_widening;
+
+                        "lowerbound"
                     } else {
                         break;
+                    };
+
+                    if stats::ENABLED {
+                        self.stats.aspiration_researches += 1;
                     }
-                } else {
+
+                    if self.debug.load(Ordering::Relaxed) {
+                        principal_variation = self.get_principal_variation(
+                            &mut board.clone(),
+                            depth,
+                            &transposition_table,
+                        );
+
+                        self.print_info(
+                            score,
+                            Some(bound),
+                            depth,
+                            pv_index,
+                            &principal_variation,
+                            &transposition_table,
+                        );
+                    }
+                }
+
+                // Maybe don't discard ?
+                let Some(score) = score else {
+                    aborted = true;
+
                     break;
+                };
+
+                if pv_index == 0 {
+                    best_move = self.best_root_move;
+
+                    result.score = score;
+                    result.depth = depth;
                 }
-            }
 
-            // Maybe don't discard ?
-            if let Some(score) = score {
-                previous_score = Some(score);
-                best_move = self.best_root_move;
+                *previous_score = Some(score);
 
                 principal_variation =
                     self.get_principal_variation(&mut board.clone(), depth, &transposition_table);
 
+                if pv_index == 0 {
+                    result.principal_variation.clone_from(&principal_variation);
+                }
+
                 if self.debug.load(Ordering::Relaxed) {
-                    self.print_info(score, start_time, depth, &principal_variation);
+                    self.print_info(
+                        score,
+                        None,
+                        depth,
+                        pv_index,
+                        &principal_variation,
+                        &transposition_table,
+                    );
                 }
+
+                // No root move at all when the game is over
+                let Some(line_move) = self.best_root_move else {
+                    break;
+                };
+
+                lines.push((line_move, score));
+
+                self.excluded_root_moves.push(line_move);
             }
-        }
 
-        if let Some(best_move) = best_move {
-            println!("bestmove {}", best_move.to_uci(CastlingMode::Standard));
+            if aborted {
+                break;
+            }
+
+            result.lines = lines;
+
+            if stats::ENABLED {
+                self.stats
+                    .iteration_nodes
+                    .push((depth, self.nodes - iteration_start_nodes));
+            }
         }
 
         self.searching.store(false, Ordering::Relaxed);
+
+        position_history.truncate(history_length);
+
+        if stats::ENABLED && self.debug.load(Ordering::Relaxed) {
+            self.stats.report(self.nodes);
+        }
+
+        result.best_move = best_move;
+        result.nodes = self.nodes;
+
+        result
+    }
+
+    /// Keeps only the root moves that preserve the tablebase result, preferring the
+    /// fastest progress towards a win so the 50-move rule is respected
+    fn filter_root_moves_by_tablebase<P: SearchPosition>(&mut self, board: &P) {
+        let (Some(tablebase), Some(board)) = (self.tablebase.as_ref(), board.as_chess()) else {
+            return;
+        };
+
+        if board.board().occupied().count() as u32 > self.tb_probe_limit {
+            return;
+        }
+
+        let tb_hits = &mut self.tb_hits;
+
+        let root_moves = tablebase_root_moves(board, &self.root_moves, |board| {
+            let probe = tablebase.probe_dtz(board);
+
+            if probe.is_some() {
+                *tb_hits += 1;
+            }
+
+            probe
+        });
+
+        if let Some(root_moves) = root_moves {
+            self.root_moves = root_moves;
+        }
+    }
+
+    /// Tablebase score of the position from the side to move's perspective
+    fn probe_tablebase<P: SearchPosition>(&mut self, board: &P, ply: u16) -> Option<i16> {
+        let tablebase = self.tablebase.as_ref()?;
+        let board = board.as_chess()?;
+
+        if board.board().occupied().count() as u32 > self.tb_probe_limit {
+            return None;
+        }
+
+        let wdl = tablebase.probe_wdl(board)?;
+
+        self.tb_hits += 1;
+
+        Some(match wdl {
+            Wdl::Win => TB_WIN - ply as i16,
+            Wdl::Loss => -TB_WIN + ply as i16,
+            Wdl::CursedWin => 1,
+            Wdl::BlessedLoss => -1,
+            Wdl::Draw => self.draw_score(ply),
+        })
+    }
+
+    // Score of a draw for the side to move, the contempt is from the root side's perspective
+    fn draw_score(&self, ply: u16) -> i16 {
+        let contempt = self.contempt.unwrap_or(0);
+
+        if ply.is_multiple_of(2) {
+            -contempt
+        } else {
+            contempt
+        }
+    }
+
+    // Repetitions are jittered so repeating isn't scored the same as every other line,
+    // except in objective analysis
+    fn repetition_score(&self, ply: u16) -> i16 {
+        match self.contempt {
+            Some(_) => self.draw_score(ply) - 1 + (self.nodes & 2) as i16,
+            None => self.draw_score(ply),
+        }
     }
 
     fn print_info(
-        &mut self,
+        &self,
         score: i16,
-        start_time: SystemTime,
+        bound: Option<&str>,
         depth: i16,
+        pv_index: usize,
         principal_variation: &[Move],
+        transposition_table: &[Option<Node>],
     ) {
-        let time_ms = start_time.elapsed().unwrap().as_millis();
-        let nodes_per_second: u64 = self.nodes / (time_ms + 1) as u64 * 1000;
+        let time_ms = self.start_time.elapsed().unwrap().as_millis() as u64;
+        let nodes_per_second: u64 = self.nodes * 1000 / (time_ms + 1);
 
         let pv_string: String = principal_variation
             .iter()
-            .map(|m| m.to_uci(CastlingMode::Standard).to_string())
+            .map(|m| m.to_uci(self.castling_mode).to_string())
             .collect::<Vec<String>>()
             .join(" ");
 
-        let score_string = if score > MATE - MATE_MAX_PLIES {
-            let mate_in_plies = MATE - score;
+        let score_string = uci_score(score);
 
-            format!("mate {}", ((mate_in_plies as f64 / 2.0).ceil() as i8))
-        } else if score < -MATE + MATE_MAX_PLIES {
-            let mate_in_plies = -MATE - score;
+        let bound_string = bound.map_or(String::new(), |bound| format!(" {bound}"));
 
-            format!("mate {}", ((mate_in_plies as f64 / 2.0).ceil() as i8))
+        let multi_pv_string = if self.multi_pv > 1 {
+            format!(" multipv {}", pv_index + 1)
         } else {
-            format!("cp {}", score)
+            String::new()
         };
 
-        println!("info depth {depth} score {score_string} time {time_ms} nodes {0} nps {nodes_per_second} pv {pv_string}", self.nodes);
+        println!(
+            "info depth {depth} seldepth {}{multi_pv_string} score {score_string}{bound_string} time {time_ms} nodes {} nps {nodes_per_second} hashfull {} tbhits {} pv {pv_string}",
+            self.seldepth,
+            self.nodes,
+            hashfull(transposition_table),
+            self.tb_hits
+        );
+    }
 
-        self.nodes = 0;
+    // Static evaluation from the side to move's perspective
+    fn evaluate<P: SearchPosition>(&self, board: &P, color: i16, ply: u16) -> i16 {
+        match self.nnue {
+            Some(ref nnue) => {
+                let evaluation = nnue.evaluate(ply, board.turn());
+
+                // The network is only used for standard chess, where known endings take over
+                match board.as_chess() {
+                    Some(chess) => match endgame::evaluate(chess.board(), chess.turn()) {
+                        Some(score) => color * score,
+                        None => endgame::scale(chess.board(), chess.turn(), evaluation),
+                    },
+                    None => evaluation,
+                }
+            }
+            None => color * board.evaluate(),
+        }
     }
 
-    fn quiesce(
+    // Depth is zero at the first quiescence ply and negative below it
+    #[allow(clippy::too_many_arguments)]
+    fn quiesce<P: SearchPosition>(
         &mut self,
-        board: &Chess,
+        board: &P,
         alpha: &mut i16,
         beta: &mut i16,
         color: i16,
         ply: u16,
+        depth: i16,
         hash: Zobrist64,
         position_history: &mut Vec<Zobrist64>,
-        transposition_table: &[Option<Node>],
+        transposition_table: &mut [Option<Node>],
     ) -> Option<i16> {
+        self.seldepth = self.seldepth.max(ply);
+
         if board.is_insufficient_material() {
-            return Some(0);
+            return Some(self.draw_score(ply));
         }
 
-        // Checkmate or stalemate
-        if board.legal_moves().is_empty() {
-            if !board.checkers().is_empty() {
-                return Some(-MATE);
-            }
+        let in_check = board.is_check();
+
+        // Evasions when in check, the only time mate has to be tested for
+        let mut moves = if in_check {
+            board.legal_moves()
+        } else {
+            board.capture_moves()
+        };
 
-            return Some(0);
+        // Checkmate or a variant's own ending
+        if let Some(score) = terminal_score(
+            board,
+            in_check && moves.is_empty(),
+            ply,
+            self.draw_score(ply),
+        ) {
+            return Some(score);
         }
         // 50-move rule
         else if board.halfmoves() >= 100 {
-            return Some(0);
+            return Some(self.draw_score(ply));
         }
         // Repetition
-        if ply > 0 {
-            let mut repetitions: u8 = 0;
+        if ply > 0 && is_repetition(position_history, board.halfmoves(), ply) {
+            return Some(self.repetition_score(ply));
+        }
+
+        let transposition_table_index: usize = hash.0 as usize % transposition_table.len();
 
-            for position in position_history.iter().rev().step_by(2) {
-                if *position == hash {
-                    repetitions += 1;
+        if stats::ENABLED {
+            self.stats.tt_probes += 1;
+        }
+
+        // Every entry is at least as deep as quiescence
+        if let Some(ref tt_node) = transposition_table[transposition_table_index] {
+            if tt_node.hash == hash {
+                let score = score_from_tt(tt_node.score, ply);
 
-                    if repetitions >= 2 {
-                        return Some(0);
+                if stats::ENABLED {
+                    self.stats.tt_hits += 1;
+                }
+
+                let cutoff = match tt_node.node_type {
+                    NodeType::Exact => true,
+                    NodeType::Lowerbound => score >= *beta,
+                    NodeType::Upperbound => score <= *alpha,
+                };
+
+                if cutoff {
+                    if stats::ENABLED {
+                        self.stats.tt_cutoffs += 1;
                     }
+
+                    return Some(score);
                 }
             }
         }
 
-        let evaluation = color * evaluate(board);
+        let original_alpha = *alpha;
 
-        let mut best_score: i16 = evaluation;
+        let mut best_score = -MATE + ply as i16;
+        let mut evaluation = best_score;
 
-        if best_score >= *beta {
-            return Some(best_score);
-        }
+        // Stand pat, not allowed when in check as every evasion may lose
+        if !in_check {
+            evaluation = self.evaluate(board, color, ply);
 
-        if best_score > *alpha {
-            *alpha = best_score;
+            best_score = evaluation;
+
+            if best_score >= *beta {
+                return Some(best_score);
+            }
+
+            if best_score > *alpha {
+                *alpha = best_score;
+            }
         }
 
-        let mut capture_moves = board.capture_moves();
+        self.sort_legal_moves(&mut moves, board, ply, hash, transposition_table);
 
-        self.sort_legal_moves(&mut capture_moves, board, hash, transposition_table);
+        // Quiet checks only at the first ply, so quiescence still terminates
+        if !in_check && depth == 0 {
+            moves.extend(board.legal_moves().into_iter().filter(|m| {
+                if m.is_capture() {
+                    return false;
+                }
 
-        for capture_move in capture_moves {
-            self.nodes += 1;
+                let mut board_clone = board.clone();
 
-            let mut board_clone = board.clone();
+                board_clone.play_unchecked(*m);
 
-            let child_hash;
+                board_clone.is_check()
+            }));
+        }
 
-            // TODO: Unmake move
-            if let Some(new_child_hash) =
-                board_clone.update_zobrist_hash(hash, capture_move, EnPassantMode::Legal)
+        let mut node: Node = Node {
+            best_move: None,
+            depth: 0,
+            hash,
+            node_type: NodeType::Upperbound,
+            score: best_score,
+        };
+
+        for quiescence_move in moves {
+            // Delta pruning, skip captures that can't bring the score back up to alpha even
+            // with a margin. Variant captures can be worth far more than the piece
+            if !in_check
+                && board.as_chess().is_some()
+                && !quiescence_move.is_promotion()
+                && quiescence_move.capture().is_some_and(|captured| {
+                    evaluation + material_value(captured) + self.params.delta_margin < *alpha
+                })
             {
-                child_hash = new_child_hash;
+                continue;
+            }
 
-                board_clone.play_unchecked(capture_move);
-            } else {
-                board_clone.play_unchecked(capture_move);
+            self.nodes += 1;
 
-                child_hash = board_clone.zobrist_hash(EnPassantMode::Legal);
+            if stats::ENABLED {
+                self.stats.quiescence_nodes += 1;
             }
 
-            position_history.push(child_hash);
+            let (board_clone, child_hash) =
+                self.play(board, hash, quiescence_move, ply, position_history);
 
             let move_score = -self.quiesce(
                 &board_clone,
@@ -244,6 +597,7 @@ impl Searcher {
                 &mut -(*alpha),
                 -color,
                 ply + 1,
+                depth - 1,
                 child_hash,
                 position_history,
                 transposition_table,
@@ -251,35 +605,55 @@ impl Searcher {
 
             position_history.pop();
 
-            if move_score >= *beta {
-                return Some(move_score);
-            }
             if move_score > best_score {
                 best_score = move_score;
-
-                // If move leads to mate
-                if best_score > MATE - MATE_MAX_PLIES {
-                    best_score -= 1;
-                } else if best_score < -MATE + MATE_MAX_PLIES {
-                    best_score += 1;
-                }
+                node.best_move = Some(quiescence_move);
+            }
+            if move_score >= *beta {
+                break;
             }
             if move_score > *alpha {
                 *alpha = move_score;
             }
 
+            if self
+                .max_nodes
+                .is_some_and(|max_nodes| self.nodes >= max_nodes)
+            {
+                self.searching.store(false, Ordering::Relaxed);
+            }
+
             if !self.searching.load(Ordering::Relaxed) {
                 return None;
             }
         }
 
+        node.score = score_to_tt(best_score, ply);
+
+        node.node_type = if best_score >= *beta {
+            NodeType::Lowerbound
+        } else if best_score > original_alpha {
+            NodeType::Exact
+        } else {
+            NodeType::Upperbound
+        };
+
+        // Store node in the transposition table, never replacing a main search entry
+        if self.searching.load(Ordering::Relaxed) {
+            match transposition_table[transposition_table_index] {
+                Some(ref tt_node) if tt_node.depth > 0 => {}
+                _ => transposition_table[transposition_table_index] = Some(node),
+            }
+        }
+
         Some(best_score)
     }
 
-    fn negamax(
+    #[allow(clippy::too_many_arguments)]
+    fn negamax<P: SearchPosition>(
         &mut self,
-        board: &Chess,
-        depth: i16,
+        board: &P,
+        mut depth: i16,
         ply: u16,
         alpha: &mut i16,
         beta: &mut i16,
@@ -290,47 +664,91 @@ impl Searcher {
     ) -> Option<i16> {
         let mut best_score = i16::MIN + 1;
 
+        self.seldepth = self.seldepth.max(ply);
+
         if board.is_insufficient_material() {
-            return Some(0);
+            return Some(self.draw_score(ply));
         }
 
         let mut legal_moves: MoveList = board.legal_moves();
 
-        // Checkmate or stalemate
-        if legal_moves.is_empty() {
-            if !board.checkers().is_empty() {
-                return Some(-MATE);
-            }
-
-            return Some(0);
+        // Checkmate, stalemate or a variant's own ending
+        if let Some(score) =
+            terminal_score(board, legal_moves.is_empty(), ply, self.draw_score(ply))
+        {
+            return Some(score);
         }
         // 50-move rule
         else if board.halfmoves() >= 100 {
-            return Some(0);
+            return Some(self.draw_score(ply));
         }
         // Repetition
-        if ply > 0 {
-            let mut repetitions: u8 = 0;
+        if ply > 0 && is_repetition(position_history, board.halfmoves(), ply) {
+            return Some(self.repetition_score(ply));
+        }
 
-            for position in position_history.iter().rev().step_by(2) {
-                if *position == hash {
-                    repetitions += 1;
+        // A reversible move back to an earlier position guarantees at least a draw. Only
+        // standard chess, where such a move is always legal
+        let draw_score = self.draw_score(ply);
 
-                    if repetitions >= 2 {
-                        return Some(0);
-                    }
-                }
+        if ply > 0
+            && *alpha < draw_score
+            && board.as_chess().is_some()
+            && has_upcoming_repetition(
+                board.board(),
+                board.turn(),
+                position_history,
+                board.halfmoves(),
+                ply,
+            )
+        {
+            *alpha = draw_score;
+
+            if *alpha >= *beta {
+                return Some(draw_score);
             }
         }
 
-        let transposition_table_index: usize = hash.0 as usize % TRANSPOSITION_TABLE_LENGTH;
+        // Endgame tablebase, only right after a capture or pawn move so the
+        // 50-move rule can't change the result
+        if ply > 0 && board.halfmoves() == 0 {
+            if let Some(score) = self.probe_tablebase(board, ply) {
+                return Some(score);
+            }
+        }
+
+        // Mate distance pruning, no line from here can beat a mate found closer to the root
+        if ply > 0 {
+            *alpha = (*alpha).max(-MATE + ply as i16);
+            *beta = (*beta).min(MATE - ply as i16 - 1);
+
+            if *alpha >= *beta {
+                return Some(*alpha);
+            }
+        }
+
+        let transposition_table_index: usize = hash.0 as usize % transposition_table.len();
+
+        // Transposition table hit, the root always searches to find a best move
+        if stats::ENABLED && ply > 0 {
+            self.stats.tt_probes += 1;
+        }
 
-        // Transposition table hit
         if let Some(ref tt_node) = transposition_table[transposition_table_index] {
-            if tt_node.hash == hash && tt_node.depth >= depth {
-                let node = tt_node.clone();
+            if stats::ENABLED && ply > 0 && tt_node.hash == hash {
+                self.stats.tt_hits += 1;
+            }
+
+            if ply > 0 && tt_node.hash == hash && tt_node.depth >= depth {
+                let mut node = tt_node.clone();
+
+                node.score = score_from_tt(node.score, ply);
 
                 if node.node_type == NodeType::Exact {
+                    if stats::ENABLED {
+                        self.stats.tt_cutoffs += 1;
+                    }
+
                     return Some(node.score);
                 } else if node.node_type == NodeType::Lowerbound {
                     *alpha = i16::max(*alpha, node.score);
@@ -338,6 +756,10 @@ impl Searcher {
                     *beta = i16::min(*beta, node.score);
                 }
                 if alpha >= beta {
+                    if stats::ENABLED {
+                        self.stats.tt_cutoffs += 1;
+                    }
+
                     return Some(node.score);
                 }
             }
@@ -350,12 +772,123 @@ impl Searcher {
                 beta,
                 color,
                 ply,
+                0,
                 hash,
                 position_history,
                 transposition_table,
             );
         }
 
+        let in_check = board.is_check();
+
+        let static_evaluation = if in_check {
+            -MATE + ply as i16
+        } else {
+            let evaluation = self.evaluate(board, color, ply);
+
+            self.correction
+                .correct(board.board(), board.turn(), evaluation)
+        };
+
+        // Razoring, a position far below alpha at shallow depth only gets a quiescence search
+        if ply > 0
+            && !in_check
+            && depth <= self.params.razoring_depth
+            && alpha.abs() < TB_WIN - MATE_MAX_PLIES
+            && static_evaluation + self.params.razoring_margin * depth < *alpha
+        {
+            let score = self.quiesce(
+                board,
+                &mut alpha.clone(),
+                &mut (*alpha + 1),
+                color,
+                ply,
+                0,
+                hash,
+                position_history,
+                transposition_table,
+            )?;
+
+            if score <= *alpha {
+                return Some(score);
+            }
+        }
+
+        // ProbCut, a good capture beating beta by a margin in a reduced search is very
+        // likely to fail high in the full one too. The exchange evaluation only holds for
+        // standard captures, not exploding, forced or pocketed ones in variants
+        let probcut_beta = beta.saturating_add(self.params.probcut_margin);
+
+        if ply > 0
+            && board.as_chess().is_some()
+            && !in_check
+            && depth >= self.params.probcut_depth
+            && beta.abs() < TB_WIN - MATE_MAX_PLIES
+        {
+            let mut capture_moves = board.capture_moves();
+
+            self.sort_legal_moves(&mut capture_moves, board, ply, hash, transposition_table);
+
+            for capture_move in capture_moves {
+                if !see(
+                    board.board(),
+                    &capture_move,
+                    probcut_beta - static_evaluation,
+                ) {
+                    continue;
+                }
+
+                self.nodes += 1;
+
+                let (board_clone, child_hash) =
+                    self.play(board, hash, capture_move, ply, position_history);
+
+                let move_score = -self.negamax(
+                    &board_clone,
+                    depth - self.params.probcut_reduction,
+                    ply + 1,
+                    &mut -probcut_beta,
+                    &mut (-probcut_beta + 1),
+                    -color,
+                    position_history,
+                    child_hash,
+                    transposition_table,
+                )?;
+
+                position_history.pop();
+
+                if move_score >= probcut_beta {
+                    return Some(move_score);
+                }
+            }
+        }
+
+        let has_tt_move = transposition_table[transposition_table_index]
+            .as_ref()
+            .is_some_and(|tt_node| tt_node.hash == hash && tt_node.best_move.is_some());
+
+        // Without a hash move to search first, either reduce the depth or run a shallower
+        // search to find one. The root is always ordered by the previous iteration
+        if ply > 0 && !has_tt_move && depth >= self.params.internal_iterative_depth {
+            match self.params.internal_iterative {
+                InternalIterative::None => {}
+                InternalIterative::Reduction => depth -= 1,
+                InternalIterative::Deepening => {
+                    self.negamax(
+                        board,
+                        depth - 2,
+                        ply,
+                        &mut alpha.clone(),
+                        &mut beta.clone(),
+                        color,
+                        position_history,
+                        hash,
+                        transposition_table,
+                    )?;
+                }
+            }
+        }
+
         // Nullmove pruning
         /*
         if !board.is_check()
@@ -394,29 +927,40 @@ impl Searcher {
             score: best_score,
         };
 
-        self.sort_legal_moves(&mut legal_moves, board, hash, transposition_table);
-
-        for legal_move in legal_moves {
-            self.nodes += 1;
+        if ply == 0 && !self.root_moves.is_empty() {
+            legal_moves.retain(|m| self.root_moves.contains(m));
+        }
 
-            let mut board_clone = board.clone();
+        if ply == 0 {
+            legal_moves.retain(|m| !self.excluded_root_moves.contains(m));
+        }
 
-            let child_hash;
+        let tt_move_first =
+            self.sort_legal_moves(&mut legal_moves, board, ply, hash, transposition_table);
 
-            // TODO: Unmake move
-            if let Some(new_child_hash) =
-                board_clone.update_zobrist_hash(hash, legal_move, EnPassantMode::Legal)
-            {
-                child_hash = new_child_hash;
+        // Searched moves that didn't cause a cutoff, penalized in the history when one does
+        let mut quiets_searched = MoveList::new();
+        let mut captures_searched = MoveList::new();
 
-                board_clone.play_unchecked(legal_move);
-            } else {
-                board_clone.play_unchecked(legal_move);
+        for (move_number, legal_move) in legal_moves.into_iter().enumerate() {
+            self.nodes += 1;
 
-                child_hash = board_clone.zobrist_hash(EnPassantMode::Legal);
+            if ply == 0
+                && self.debug.load(Ordering::Relaxed)
+                && self
+                    .start_time
+                    .elapsed()
+                    .is_ok_and(|elapsed| elapsed.as_millis() >= 1000)
+            {
+                println!(
+                    "info depth {depth} currmove {} currmovenumber {}",
+                    legal_move.to_uci(self.castling_mode),
+                    move_number + 1
+                );
             }
 
-            position_history.push(child_hash);
+            let (board_clone, child_hash) =
+                self.play(board, hash, legal_move, ply, position_history);
 
             let extension: i16 = if board_clone.is_check() { 1 } else { 0 };
 
@@ -448,27 +992,70 @@ impl Searcher {
 
                     node.node_type = NodeType::Exact;
                 }
-
-                // If move leads to mate
-                if best_score > MATE - MATE_MAX_PLIES {
-                    best_score -= 1;
-                } else if best_score < -MATE + MATE_MAX_PLIES {
-                    best_score += 1;
-                }
             }
 
             if best_score >= *beta {
                 node.node_type = NodeType::Lowerbound;
 
+                if stats::ENABLED {
+                    let stage = if move_number == 0 && tt_move_first {
+                        Stage::TranspositionTable
+                    } else if legal_move.is_capture() || legal_move.is_promotion() {
+                        Stage::Capture
+                    } else {
+                        Stage::Quiet
+                    };
+
+                    self.stats.beta_cutoff(move_number, stage);
+                }
+
+                self.update_history(
+                    board.turn(),
+                    legal_move,
+                    depth,
+                    ply,
+                    &quiets_searched,
+                    &captures_searched,
+                );
+
                 break;
             }
 
+            if legal_move.is_capture() {
+                captures_searched.push(legal_move);
+            } else {
+                quiets_searched.push(legal_move);
+            }
+
+            if self
+                .max_nodes
+                .is_some_and(|max_nodes| self.nodes >= max_nodes)
+            {
+                self.searching.store(false, Ordering::Relaxed);
+            }
+
             if !self.searching.load(Ordering::Relaxed) {
                 return None;
             }
         }
 
-        node.score = best_score;
+        // Learn how far the static evaluation was from the search result, as long as the
+        // bound says which way. Captures are left to quiescence
+        if !in_check
+            && node.best_move.is_none_or(|m| !m.is_capture())
+            && best_score.abs() < TB_WIN - MATE_MAX_PLIES
+            && !(node.node_type == NodeType::Lowerbound && best_score <= static_evaluation)
+            && !(node.node_type == NodeType::Upperbound && best_score >= static_evaluation)
+        {
+            self.correction.update(
+                board.board(),
+                board.turn(),
+                depth,
+                best_score as i32 - static_evaluation as i32,
+            );
+        }
+
+        node.score = score_to_tt(best_score, ply);
 
         // Store node in the transposition table
         if self.searching.load(Ordering::Relaxed) {
@@ -484,62 +1071,145 @@ impl Searcher {
         Some(best_score)
     }
 
-    fn sort_legal_moves(
+    fn update_history(
+        &mut self,
+        turn: Color,
+        best_move: Move,
+        depth: i16,
+        ply: u16,
+        quiets_searched: &[Move],
+        captures_searched: &[Move],
+    ) {
+        let bonus = history::bonus(depth);
+        let previous = self.previous_moves(ply);
+
+        if best_move.is_capture() {
+            self.history.update_capture(&best_move, turn, bonus);
+        } else {
+            self.history
+                .update_quiet(&best_move, turn, &previous, bonus);
+
+            for quiet in quiets_searched {
+                self.history.update_quiet(quiet, turn, &previous, -bonus);
+            }
+        }
+
+        for capture in captures_searched {
+            self.history.update_capture(capture, turn, -bonus);
+        }
+    }
+
+    // Plays a move on a copy of the board, updating the network and the history
+    fn play<P: SearchPosition>(
+        &mut self,
+        board: &P,
+        hash: Zobrist64,
+        m: Move,
+        ply: u16,
+        position_history: &mut Vec<Zobrist64>,
+    ) -> (P, Zobrist64) {
+        let mut board_clone = board.clone();
+
+        let child_hash;
+
+        // TODO: Unmake move
+        if let Some(new_child_hash) = board_clone.update_zobrist_hash(hash, m, EnPassantMode::Legal)
+        {
+            child_hash = new_child_hash;
+
+            board_clone.play_unchecked(m);
+        } else {
+            board_clone.play_unchecked(m);
+
+            child_hash = board_clone.zobrist_hash(EnPassantMode::Legal);
+        }
+
+        if let Some(ref mut nnue) = self.nnue {
+            nnue.make_move(board.turn(), m, ply);
+        }
+
+        position_history.push(child_hash);
+
+        let ply = ply as usize;
+
+        if self.stack.len() <= ply {
+            self.stack.resize(ply + 1, StackEntry::default());
+        }
+
+        self.stack[ply] = StackEntry {
+            current_move: Some(m),
+            moved_piece: Some(Piece {
+                color: board.turn(),
+                role: m.role(),
+            }),
+        };
+
+        (board_clone, child_hash)
+    }
+
+    // Pieces moved one and two plies before the given one
+    fn previous_moves(&self, ply: u16) -> [MovedPiece; 2] {
+        [1, 2].map(|back| {
+            let entry = self.stack.get((ply as usize).checked_sub(back)?)?;
+
+            Some((entry.moved_piece?, entry.current_move?.to()))
+        })
+    }
+
+    fn sort_legal_moves<P: SearchPosition>(
         &self,
         legal_moves: &mut MoveList,
-        board: &Chess,
+        board: &P,
+        ply: u16,
         hash: Zobrist64,
         transposition_table: &[Option<Node>],
-    ) {
+    ) -> bool {
         if legal_moves.is_empty() {
-            return;
+            return false;
         }
 
+        let mut tt_move_first = false;
+
         // Move best move to the front
-        if let Some(ref pv_node) = transposition_table[hash.0 as usize % TRANSPOSITION_TABLE_LENGTH]
+        if let Some(ref pv_node) = transposition_table[hash.0 as usize % transposition_table.len()]
         {
             if let Some(best_move) = pv_node.best_move {
                 if board.is_legal(best_move) {
                     if let Some(pos) = legal_moves.iter().position(|m| *m == best_move) {
                         legal_moves.swap(0, pos);
+
+                        tt_move_first = true;
                     }
                 }
             }
         }
 
+        let turn = board.turn();
+        let previous = self.previous_moves(ply);
+
         // Score each move for sorting
-        legal_moves[1..].sort_by_cached_key(|m| {
+        legal_moves[tt_move_first as usize..].sort_by_cached_key(|m| {
             if m.is_promotion() {
-                return 0i16; // Promotions first
+                return i32::MIN; // Promotions first
             }
             if m.is_capture() {
-                // MVV-LVA: victim value - attacker value
-                let victim = match m.capture().unwrap() {
-                    Role::Pawn => 100,
-                    Role::Knight => 300,
-                    Role::Bishop => 350,
-                    Role::Rook => 500,
-                    Role::Queen => 900,
-                    Role::King => return 0, // shouldn't happen
-                };
-                let attacker = match m.role() {
-                    Role::Pawn => 100,
-                    Role::Knight => 300,
-                    Role::Bishop => 350,
-                    Role::Rook => 500,
-                    Role::Queen => 900,
-                    Role::King => 2000,
-                };
-                return -(victim - attacker + 1000); // negative = higher priority; +1000 ensures captures beat quiets
+                // MVV-LVA: victim value - attacker value, refined by the capture history
+                let victim = self.params.mvv_lva(m.capture().unwrap());
+                let attacker = self.params.mvv_lva(m.role());
+                return -(1_000_000
+                    + (victim - attacker) as i32 * 16
+                    + self.history.capture_score(m, turn) / 8); // -1000000 ensures captures beat quiets
             }
-            1000i16 // quiet moves last
+            -self.history.quiet_score(m, turn, &previous) // quiet moves last
         });
+
+        tt_move_first
     }
 
     // Should probably switch to a different method
-    fn get_principal_variation(
+    fn get_principal_variation<P: SearchPosition>(
         &self,
-        board: &mut Chess,
+        board: &mut P,
         depth: i16,
         transposition_table: &[Option<Node>],
     ) -> Vec<Move> {
@@ -551,7 +1221,7 @@ impl Searcher {
             hash = board.zobrist_hash::<Zobrist64>(EnPassantMode::Legal);
 
             if let Some(ref pv_node) =
-                transposition_table[hash.0 as usize % TRANSPOSITION_TABLE_LENGTH]
+                transposition_table[hash.0 as usize % transposition_table.len()]
             {
                 if let Some(best_move) = pv_node.best_move {
                     if board.is_legal(best_move) {
@@ -572,3 +1242,270 @@ impl Searcher {
         pv
     }
 }
+
+// Mate and tablebase scores are stored relative to the node instead of the root
+fn score_to_tt(score: i16, ply: u16) -> i16 {
+    if score >= TB_WIN - MATE_MAX_PLIES {
+        score + ply as i16
+    } else if score <= -TB_WIN + MATE_MAX_PLIES {
+        score - ply as i16
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i16, ply: u16) -> i16 {
+    if score >= TB_WIN - MATE_MAX_PLIES {
+        score - ply as i16
+    } else if score <= -TB_WIN + MATE_MAX_PLIES {
+        score + ply as i16
+    } else {
+        score
+    }
+}
+
+/// Root moves that preserve the tablebase result, out of those already allowed, given a DTZ
+/// probe of the position after each move. None when any of them can't be probed
+fn tablebase_root_moves(
+    board: &Chess,
+    root_moves: &[Move],
+    mut probe_dtz: impl FnMut(&Chess) -> Option<(Wdl, i32)>,
+) -> Option<Vec<Move>> {
+    let mut ranked_moves: Vec<(Move, Wdl, i32)> = Vec::new();
+
+    for legal_move in board.legal_moves() {
+        if !root_moves.is_empty() && !root_moves.contains(&legal_move) {
+            continue;
+        }
+
+        let mut board_clone = board.clone();
+
+        board_clone.play_unchecked(legal_move);
+
+        // Distance to zeroing from the root, a capture or pawn move zeroes the clock itself
+        // and a mate ends the game, so neither is ranked behind a slower win
+        let (wdl, dtz) = if board_clone.is_checkmate() {
+            (Wdl::Loss, 1)
+        } else {
+            let (wdl, dtz) = probe_dtz(&board_clone)?;
+
+            if legal_move.is_zeroing() {
+                (wdl, 1)
+            } else {
+                (wdl, dtz.abs() + 1)
+            }
+        };
+
+        ranked_moves.push((legal_move, -wdl, dtz));
+    }
+
+    let best_wdl = ranked_moves.iter().map(|(_, wdl, _)| *wdl).max()?;
+
+    ranked_moves.retain(|(_, wdl, _)| *wdl == best_wdl);
+
+    // Zero the clock as soon as possible when winning, as late as possible when losing
+    if best_wdl >= Wdl::CursedWin {
+        let fastest = ranked_moves.iter().map(|(_, _, dtz)| *dtz).min().unwrap();
+
+        ranked_moves.retain(|(_, _, dtz)| *dtz == fastest);
+    } else if best_wdl <= Wdl::BlessedLoss {
+        let slowest = ranked_moves.iter().map(|(_, _, dtz)| *dtz).max().unwrap();
+
+        ranked_moves.retain(|(_, _, dtz)| *dtz == slowest);
+    }
+
+    Some(ranked_moves.into_iter().map(|(m, _, _)| m).collect())
+}
+
+/// Score as given in UCI info lines, `mate N` in moves for mates and `cp N` otherwise
+fn uci_score(score: i16) -> String {
+    if score > MATE - MATE_MAX_PLIES {
+        let mate_in_plies = MATE - score;
+
+        format!("mate {}", ((mate_in_plies as f64 / 2.0).ceil() as i8))
+    } else if score < -MATE + MATE_MAX_PLIES {
+        let mate_in_plies = -MATE - score;
+
+        format!("mate {}", ((mate_in_plies as f64 / 2.0).ceil() as i8))
+    } else {
+        format!("cp {}", score)
+    }
+}
+
+/// Permille of the first thousand entries in use
+fn hashfull(transposition_table: &[Option<Node>]) -> usize {
+    let sample = &transposition_table[..transposition_table.len().min(1000)];
+
+    sample.iter().filter(|node| node.is_some()).count() * 1000 / sample.len().max(1)
+}
+
+/// Score of a finished game from the side to move's perspective
+fn terminal_score<P: Position>(
+    board: &P,
+    no_legal_moves: bool,
+    ply: u16,
+    draw: i16,
+) -> Option<i16> {
+    // Mates are scored by their distance from the root, so shorter mates are preferred
+    let mate = MATE - ply as i16;
+
+    if board.is_variant_end() || no_legal_moves {
+        match board.variant_outcome() {
+            Outcome::Known(KnownOutcome::Decisive { winner }) => {
+                return Some(if winner == board.turn() { mate } else { -mate });
+            }
+            Outcome::Known(KnownOutcome::Draw) => return Some(draw),
+            Outcome::Unknown => {}
+        }
+    }
+
+    if no_legal_moves {
+        if board.is_check() {
+            return Some(-mate);
+        }
+
+        return Some(draw);
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::fen::Fen;
+
+    fn search(
+        fen: &str,
+        transposition_table: &mut Arc<Mutex<Vec<Option<Node>>>>,
+    ) -> (String, Option<String>) {
+        let board: Chess = Fen::from_ascii(fen.as_bytes())
+            .unwrap()
+            .into_position(CastlingMode::Standard)
+            .unwrap();
+
+        let mut searcher = Searcher::new(
+            Arc::new(AtomicBool::new(true)),
+            Arc::new(AtomicBool::new(false)),
+        );
+
+        searcher.max_depth = Some(7);
+
+        let mut position_history = vec![board.zobrist_hash(EnPassantMode::Legal)];
+
+        let result = searcher.search(board, &mut position_history, transposition_table);
+
+        (
+            uci_score(result.score),
+            result
+                .best_move
+                .map(|m| m.to_uci(CastlingMode::Standard).to_string()),
+        )
+    }
+
+    fn transposition_table() -> Arc<Mutex<Vec<Option<Node>>>> {
+        Arc::new(Mutex::new(vec![None; 1 << 18]))
+    }
+
+    #[test]
+    fn forced_mates() {
+        let mates = [
+            ("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "mate 1", "a1a8"),
+            (
+                "r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 1",
+                "mate 2",
+                "d5f6",
+            ),
+            ("r5rk/5p1p/5R2/4B3/8/8/7P/7K w - - 0 1", "mate 3", "f6a6"),
+            (
+                "2r3k1/p4p2/3Rp2p/1p2P1pK/8/1P4P1/P3Q2P/1q6 b - - 0 1",
+                "mate 3",
+                "b1g6",
+            ),
+        ];
+
+        for (fen, score, best_move) in mates {
+            let result = search(fen, &mut transposition_table());
+
+            assert_eq!(
+                result,
+                (score.to_string(), Some(best_move.to_string())),
+                "{fen}"
+            );
+        }
+
+        // Being mated, after 1. Nf6+ gxf6 2. Bxf7#
+        let (score, _) = search(
+            "r2qkb1r/pp2nppp/3p1N2/2p1N1B1/2BnP3/3P4/PPP2PPP/R2bK2R b KQkq - 2 1",
+            &mut transposition_table(),
+        );
+
+        assert_eq!(score, "mate -1");
+    }
+
+    // Mates stored at one ply are read back at another when the table is shared between a
+    // position and the one two plies later, so their distances have to be adjusted
+    #[test]
+    fn mates_from_the_transposition_table() {
+        let root = "2r3k1/p4p2/3Rp2p/1p2P1pK/8/1P4P1/P3Q2P/1q6 b - - 0 1";
+        let after_two_plies = "2r3k1/p4p2/3Rp1qp/1p2P1p1/6K1/1P4P1/P3Q2P/8 b - - 2 2";
+
+        let mut transposition_table = transposition_table();
+
+        assert_eq!(
+            search(root, &mut transposition_table),
+            ("mate 3".to_string(), Some("b1g6".to_string()))
+        );
+        assert_eq!(
+            search(after_two_plies, &mut transposition_table).0,
+            "mate 2"
+        );
+
+        let mut transposition_table = self::transposition_table();
+
+        assert_eq!(
+            search(after_two_plies, &mut transposition_table).0,
+            "mate 2"
+        );
+        assert_eq!(
+            search(root, &mut transposition_table),
+            ("mate 3".to_string(), Some("b1g6".to_string()))
+        );
+    }
+
+    // Every move probes as a win with a long way to zeroing, so only the mate and the capture
+    // reach zeroing on the next move
+    #[test]
+    fn tablebase_mate_in_one_beside_a_winning_capture() {
+        let board: Chess = Fen::from_ascii(b"6k1/5ppp/8/8/8/8/8/Rn4K1 w - - 0 1")
+            .unwrap()
+            .into_position(CastlingMode::Standard)
+            .unwrap();
+
+        let probe = |_: &Chess| Some((Wdl::Loss, -9));
+
+        let uci = |moves: Vec<Move>| -> Vec<String> {
+            moves
+                .iter()
+                .map(|m| m.to_uci(CastlingMode::Standard).to_string())
+                .collect()
+        };
+
+        let root_moves = tablebase_root_moves(&board, &[], probe).unwrap();
+
+        assert_eq!(uci(root_moves), ["a1b1", "a1a8"]);
+
+        // Moves given by searchmoves are narrowed down, never replaced
+        let searchmoves: Vec<Move> = board
+            .legal_moves()
+            .into_iter()
+            .filter(|m| {
+                ["a1a8", "a1a2"].contains(&m.to_uci(CastlingMode::Standard).to_string().as_str())
+            })
+            .collect();
+
+        let root_moves = tablebase_root_moves(&board, &searchmoves, probe).unwrap();
+
+        assert_eq!(uci(root_moves), ["a1a8"]);
+    }
+}
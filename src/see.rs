@@ -0,0 +1,96 @@
+use crate::evaluate::material_value;
+use shakmaty::{Board, Move, Role, Square};
+
+const ROLES: [Role; 6] = [
+    Role::Pawn,
+    Role::Knight,
+    Role::Bishop,
+    Role::Rook,
+    Role::Queen,
+    Role::King,
+];
+
+/// Whether the exchange started by a move on its target square wins at least the threshold,
+/// with both sides always recapturing with their least valuable attacker. Pins are ignored
+pub fn see(board: &Board, m: &Move, threshold: i16) -> bool {
+    // Castling and drops don't start an exchange
+    let (Some(from), false) = (m.from(), m.is_castle()) else {
+        return threshold <= 0;
+    };
+
+    let to = m.to();
+
+    let mut swap = m.capture().map_or(0, value) - threshold;
+
+    if swap < 0 {
+        return false;
+    }
+
+    swap = value(m.role()) - swap;
+
+    if swap <= 0 {
+        return true;
+    }
+
+    let mut occupied = board.occupied() ^ from ^ to;
+
+    if m.is_en_passant() {
+        occupied ^= Square::from_coords(to.file(), from.rank());
+    }
+
+    let mut turn = board.color_at(from).expect("piece on the from square");
+
+    let mut result = true;
+
+    loop {
+        turn = !turn;
+
+        // Pieces are removed from the occupancy as they capture, revealing the ones behind
+        let attackers = (board.attacks_to(to, turn, occupied)
+            | board.attacks_to(to, !turn, occupied))
+            & occupied;
+
+        let own_attackers = attackers & board.by_color(turn);
+
+        if own_attackers.is_empty() {
+            break;
+        }
+
+        result = !result;
+
+        let (role, attacker) = ROLES
+            .iter()
+            .find_map(|&role| {
+                (own_attackers & board.by_role(role))
+                    .first()
+                    .map(|square| (role, square))
+            })
+            .expect("attacker of some role");
+
+        // The king can only capture last
+        if role == Role::King {
+            return if (attackers & !board.by_color(turn)).any() {
+                !result
+            } else {
+                result
+            };
+        }
+
+        swap = value(role) - swap;
+
+        if swap < result as i16 {
+            break;
+        }
+
+        occupied ^= attacker;
+    }
+
+    result
+}
+
+fn value(role: Role) -> i16 {
+    match role {
+        Role::King => 10000,
+        _ => material_value(role),
+    }
+}
@@ -0,0 +1,118 @@
+/// Results of game pairs with swapped colours, counted by the first engine's score in
+/// the pair: 0, 0.5, 1, 1.5 or 2 points
+#[derive(Default, Clone, Copy)]
+pub struct Pentanomial {
+    pub counts: [u64; 5],
+}
+
+impl Pentanomial {
+    /// Adds a pair from the first engine's points in each game (0, 1 or 2 half points)
+    pub fn add(&mut self, first: u8, second: u8) {
+        self.counts[(first + second) as usize] += 1;
+    }
+
+    pub fn pairs(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    // Mean and variance of the score per game of a pair
+    fn mean_and_variance(&self) -> Option<(f64, f64)> {
+        let pairs = self.pairs() as f64;
+
+        if pairs == 0.0 {
+            return None;
+        }
+
+        let scores = [0.0, 0.25, 0.5, 0.75, 1.0];
+
+        let mean: f64 = self
+            .counts
+            .iter()
+            .zip(scores)
+            .map(|(count, score)| *count as f64 * score)
+            .sum::<f64>()
+            / pairs;
+
+        let variance: f64 = self
+            .counts
+            .iter()
+            .zip(scores)
+            .map(|(count, score)| *count as f64 * (score - mean).powi(2))
+            .sum::<f64>()
+            / pairs;
+
+        Some((mean, variance))
+    }
+
+    /// Elo difference and its 95% confidence interval
+    pub fn elo(&self) -> Option<(f64, f64)> {
+        let (mean, variance) = self.mean_and_variance()?;
+
+        let margin = 1.96 * (variance / self.pairs() as f64).sqrt();
+
+        let elo = score_to_elo(mean);
+        let upper = score_to_elo((mean + margin).min(0.999));
+        let lower = score_to_elo((mean - margin).max(0.001));
+
+        Some((elo, (upper - lower) / 2.0))
+    }
+
+    /// Generalized SPRT log-likelihood ratio of elo1 against elo0 with the normal
+    /// approximation of the pentanomial model
+    pub fn llr(&self, elo0: f64, elo1: f64) -> f64 {
+        let Some((mean, variance)) = self.mean_and_variance() else {
+            return 0.0;
+        };
+
+        if variance <= 0.0 {
+            return 0.0;
+        }
+
+        let score0 = elo_to_score(elo0);
+        let score1 = elo_to_score(elo1);
+
+        self.pairs() as f64 * (score1 - score0) * (2.0 * mean - score0 - score1) / (2.0 * variance)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Decision {
+    AcceptH0,
+    AcceptH1,
+}
+
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub lower_bound: f64,
+    pub upper_bound: f64,
+}
+
+impl Sprt {
+    pub fn new(elo0: f64, elo1: f64, alpha: f64, beta: f64) -> Sprt {
+        Sprt {
+            elo0,
+            elo1,
+            lower_bound: (beta / (1.0 - alpha)).ln(),
+            upper_bound: ((1.0 - beta) / alpha).ln(),
+        }
+    }
+
+    pub fn decision(&self, llr: f64) -> Option<Decision> {
+        if llr >= self.upper_bound {
+            Some(Decision::AcceptH1)
+        } else if llr <= self.lower_bound {
+            Some(Decision::AcceptH0)
+        } else {
+            None
+        }
+    }
+}
+
+fn elo_to_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+fn score_to_elo(score: f64) -> f64 {
+    -400.0 * (1.0 / score.clamp(0.001, 0.999) - 1.0).log10()
+}
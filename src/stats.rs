@@ -0,0 +1,109 @@
+// Counting is compiled out unless the stats feature is enabled
+pub const ENABLED: bool = cfg!(feature = "stats");
+
+/// Where a move causing a beta cutoff came from in the move ordering
+#[derive(Clone, Copy)]
+pub enum Stage {
+    TranspositionTable,
+    Capture,
+    Quiet,
+}
+
+/// Counters collected by the search when built with the stats feature
+#[derive(Default)]
+pub struct SearchStats {
+    pub tt_probes: u64,
+    pub tt_hits: u64,
+    pub tt_cutoffs: u64,
+    pub beta_cutoffs: u64,
+    pub first_move_cutoffs: u64,
+    pub stage_cutoffs: [u64; 3],
+    pub quiescence_nodes: u64,
+    pub aspiration_researches: u64,
+    // Nodes searched by each completed iteration
+    pub iteration_nodes: Vec<(i16, u64)>,
+}
+
+impl SearchStats {
+    pub fn beta_cutoff(&mut self, move_number: usize, stage: Stage) {
+        self.beta_cutoffs += 1;
+
+        if move_number == 0 {
+            self.first_move_cutoffs += 1;
+        }
+
+        self.stage_cutoffs[stage as usize] += 1;
+    }
+
+    /// Prints the statistics as a table of info strings
+    pub fn report(&self, nodes: u64) {
+        let rows = [
+            ("tt probes", self.tt_probes.to_string()),
+            (
+                "tt hits",
+                format!(
+                    "{} ({})",
+                    self.tt_hits,
+                    percent(self.tt_hits, self.tt_probes)
+                ),
+            ),
+            (
+                "tt cutoffs",
+                format!(
+                    "{} ({})",
+                    self.tt_cutoffs,
+                    percent(self.tt_cutoffs, self.tt_probes)
+                ),
+            ),
+            ("beta cutoffs", self.beta_cutoffs.to_string()),
+            (
+                "first move cutoffs",
+                percent(self.first_move_cutoffs, self.beta_cutoffs),
+            ),
+            (
+                "tt move cutoffs",
+                percent(self.stage_cutoffs[0], self.beta_cutoffs),
+            ),
+            (
+                "capture cutoffs",
+                percent(self.stage_cutoffs[1], self.beta_cutoffs),
+            ),
+            (
+                "quiet cutoffs",
+                percent(self.stage_cutoffs[2], self.beta_cutoffs),
+            ),
+            (
+                "quiescence nodes",
+                format!(
+                    "{} ({})",
+                    self.quiescence_nodes,
+                    percent(self.quiescence_nodes, nodes)
+                ),
+            ),
+            (
+                "aspiration researches",
+                self.aspiration_researches.to_string(),
+            ),
+        ];
+
+        println!("info string {:<24}{:>20}", "statistic", "value");
+
+        for (name, value) in rows {
+            println!("info string {name:<24}{value:>20}");
+        }
+
+        println!("info string {:<24}{:>20}", "depth", "branching factor");
+
+        for pair in self.iteration_nodes.windows(2) {
+            let ((_, previous), (depth, nodes)) = (pair[0], pair[1]);
+
+            let branching_factor = nodes as f64 / previous.max(1) as f64;
+
+            println!("info string {depth:<24}{branching_factor:>20.2}");
+        }
+    }
+}
+
+fn percent(part: u64, total: u64) -> String {
+    format!("{:.1}%", part as f64 * 100.0 / total.max(1) as f64)
+}
@@ -0,0 +1,73 @@
+use shakmaty::{Chess, Position};
+use shakmaty_syzygy::AmbiguousWdl;
+use std::path::PathBuf;
+
+/// Win/draw/loss from the side to move's perspective, cursed wins and blessed
+/// losses are decided by the 50-move rule
+pub use shakmaty_syzygy::Wdl;
+
+/// Syzygy tables found under the configured paths, opened lazily when first probed
+pub struct Tablebase {
+    tables: shakmaty_syzygy::Tablebase<Chess>,
+    directories: Vec<PathBuf>,
+}
+
+impl Tablebase {
+    /// Opens every directory in a path list separated by ':' (';' on Windows)
+    pub fn open(paths: &str) -> Result<Tablebase, String> {
+        let separator = if cfg!(windows) { ';' } else { ':' };
+
+        let mut tables = shakmaty_syzygy::Tablebase::new();
+        let mut directories: Vec<PathBuf> = Vec::new();
+
+        for path in paths.split(separator).filter(|p| !p.is_empty()) {
+            tables
+                .add_directory(path)
+                .map_err(|error| format!("{path}: {error}"))?;
+
+            directories.push(PathBuf::from(path));
+        }
+
+        Ok(Tablebase {
+            tables,
+            directories,
+        })
+    }
+
+    pub fn directories(&self) -> &[PathBuf] {
+        &self.directories
+    }
+
+    /// Largest number of pieces covered by the tables found
+    pub fn max_pieces(&self) -> u32 {
+        self.tables.max_pieces() as u32
+    }
+
+    /// Result of a position reached by a capture or pawn move, only needs the WDL tables
+    pub fn probe_wdl(&self, board: &Chess) -> Option<Wdl> {
+        if !self.can_probe(board) {
+            return None;
+        }
+
+        self.tables.probe_wdl_after_zeroing(board).ok()
+    }
+
+    /// Result of a position given its halfmove clock, and the distance to zeroing the clock
+    /// signed by that result. Needs both the WDL and DTZ tables
+    pub fn probe_dtz(&self, board: &Chess) -> Option<(Wdl, i32)> {
+        if !self.can_probe(board) {
+            return None;
+        }
+
+        let dtz = self.tables.probe_dtz(board).ok()?;
+
+        // Rounded distances close to the 50-move limit are taken as reached after zeroing
+        let wdl = AmbiguousWdl::from_dtz_and_halfmoves(dtz, board.halfmoves()).after_zeroing();
+
+        Some((wdl, dtz.ignore_rounding().0))
+    }
+
+    fn can_probe(&self, board: &Chess) -> bool {
+        board.board().occupied().count() <= self.tables.max_pieces() && !board.castles().any()
+    }
+}
@@ -7,6 +7,11 @@ use std::{
     time::{Duration, SystemTime},
 };
 
+/// Time to spend on a move from the remaining time and the increment
+pub fn allocate(remaining: u64, increment: u64) -> u64 {
+    remaining / 20 + increment / 2
+}
+
 pub fn search_for_ms(move_time: u64, searching: Arc<AtomicBool>) {
     let start_time = SystemTime::now();
 
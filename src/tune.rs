@@ -0,0 +1,298 @@
+use crate::epd::Epd;
+use crate::evaluate::{
+    features, BISHOP_PAIR_OFFSET, MATERIAL_OFFSET, PIECE_SQUARE_LATE_OFFSET, PIECE_SQUARE_OFFSET,
+    WEIGHTS, WEIGHT_COUNT,
+};
+use shakmaty::{fen::Fen, CastlingMode, Chess};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::thread;
+
+const LEARNING_RATE: f64 = 1.0;
+const BETA_1: f64 = 0.9;
+const BETA_2: f64 = 0.999;
+const EPSILON: f64 = 1e-8;
+
+const TABLE_NAMES: [&str; 6] = ["PAWN", "KNIGHT", "BISHOP", "ROOK", "QUEEN", "KING"];
+
+struct Entry {
+    features: Vec<(u16, i8)>,
+    result: f64,
+}
+
+/// Usage: tune <dataset> [epochs] [output]
+///
+/// The dataset holds one quiet position per line with the game result from white's
+/// perspective, either as EPD (`c9 "1-0";` or `[1.0]`) or as CSV (`<fen>,<result>`).
+pub fn run(args: &[String]) {
+    let Some(dataset_path) = args.first() else {
+        eprintln!("usage: reggz tune <dataset> [epochs] [output]");
+        return;
+    };
+
+    let epochs: usize = args.get(1).and_then(|v| v.parse().ok()).unwrap_or(1000);
+    let output_path = args.get(2);
+
+    let entries = match load_dataset(dataset_path) {
+        Ok(entries) => entries,
+        Err(error) => {
+            eprintln!("failed to read {dataset_path}: {error}");
+            return;
+        }
+    };
+
+    if entries.is_empty() {
+        eprintln!("no positions found in {dataset_path}");
+        return;
+    }
+
+    eprintln!("loaded {} positions", entries.len());
+
+    let mut weights: Vec<f64> = WEIGHTS.iter().map(|w| *w as f64).collect();
+
+    let k = find_k(&entries, &weights);
+
+    eprintln!("k {k:.4} error {:.6}", error(&entries, &weights, k));
+
+    let mut m = vec![0.0; WEIGHT_COUNT];
+    let mut v = vec![0.0; WEIGHT_COUNT];
+
+    for epoch in 1..=epochs {
+        let gradient = gradient(&entries, &weights, k);
+
+        for i in 0..WEIGHT_COUNT {
+            m[i] = BETA_1 * m[i] + (1.0 - BETA_1) * gradient[i];
+            v[i] = BETA_2 * v[i] + (1.0 - BETA_2) * gradient[i] * gradient[i];
+
+            let m_hat = m[i] / (1.0 - BETA_1.powi(epoch as i32));
+            let v_hat = v[i] / (1.0 - BETA_2.powi(epoch as i32));
+
+            weights[i] -= LEARNING_RATE * m_hat / (v_hat.sqrt() + EPSILON);
+        }
+
+        if epoch % 50 == 0 || epoch == epochs {
+            eprintln!("epoch {epoch} error {:.6}", error(&entries, &weights, k));
+        }
+    }
+
+    let source = emit_source(&weights);
+
+    match output_path {
+        Some(path) => {
+            if let Err(error) = fs::write(path, source) {
+                eprintln!("failed to write {path}: {error}");
+            }
+        }
+        None => print!("{source}"),
+    }
+}
+
+fn load_dataset(path: &str) -> std::io::Result<Vec<Entry>> {
+    let reader = BufReader::new(File::open(path)?);
+
+    let mut entries: Vec<Entry> = Vec::new();
+
+    for line in reader.lines() {
+        let line = line?;
+
+        if let Some((board, result)) = parse_line(&line) {
+            entries.push(Entry {
+                features: features(&board),
+                result,
+            });
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Parses a position and the game result from an EPD or CSV line
+pub fn parse_line(line: &str) -> Option<(Chess, f64)> {
+    let line = line.trim();
+
+    if let Some(entry) = parse_epd(line) {
+        return Some(entry);
+    }
+
+    // EPD operations end in semicolons, so only lines without any can be CSV
+    if line.contains(';') {
+        return None;
+    }
+
+    let (fen_string, result) = line.rsplit_once(',')?;
+
+    let fen = Fen::from_ascii(fen_string.trim().as_bytes()).ok()?;
+    let board: Chess = fen.into_position(CastlingMode::Standard).ok()?;
+
+    Some((board, parse_result(result.trim())?))
+}
+
+fn parse_epd(line: &str) -> Option<(Chess, f64)> {
+    let epd = Epd::parse(line)?;
+
+    // The result follows the position in brackets, as in `[1-0]`, or is a c9 operation
+    let bracketed = epd
+        .operations
+        .iter()
+        .find_map(|(opcode, _)| opcode.strip_prefix('[').and_then(|r| r.strip_suffix(']')));
+
+    let result = parse_result(bracketed.or_else(|| epd.operation("c9"))?)?;
+
+    Some((epd.board, result))
+}
+
+fn parse_result(result: &str) -> Option<f64> {
+    match result {
+        "1-0" => Some(1.0),
+        "0-1" => Some(0.0),
+        "1/2-1/2" => Some(0.5),
+        _ => result.parse().ok().filter(|r| (0.0..=1.0).contains(r)),
+    }
+}
+
+fn sigmoid(k: f64, score: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * score / 400.0))
+}
+
+fn score(entry: &Entry, weights: &[f64]) -> f64 {
+    entry
+        .features
+        .iter()
+        .map(|(index, coefficient)| weights[*index as usize] * *coefficient as f64)
+        .sum()
+}
+
+fn chunk_size(entries: &[Entry]) -> usize {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+
+    entries.len().div_ceil(threads).max(1)
+}
+
+fn error(entries: &[Entry], weights: &[f64], k: f64) -> f64 {
+    let total: f64 = thread::scope(|scope| {
+        let handles: Vec<_> = entries
+            .chunks(chunk_size(entries))
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|entry| (entry.result - sigmoid(k, score(entry, weights))).powi(2))
+                        .sum::<f64>()
+                })
+            })
+            .collect();
+
+        handles.into_iter().map(|h| h.join().unwrap()).sum()
+    });
+
+    total / entries.len() as f64
+}
+
+fn gradient(entries: &[Entry], weights: &[f64], k: f64) -> Vec<f64> {
+    let mut gradient = thread::scope(|scope| {
+        let handles: Vec<_> = entries
+            .chunks(chunk_size(entries))
+            .map(|chunk| {
+                scope.spawn(move || {
+                    let mut gradient = vec![0.0; WEIGHT_COUNT];
+
+                    for entry in chunk {
+                        let s = sigmoid(k, score(entry, weights));
+                        let delta = (entry.result - s) * s * (1.0 - s);
+
+                        for (index, coefficient) in &entry.features {
+                            gradient[*index as usize] += delta * *coefficient as f64;
+                        }
+                    }
+
+                    gradient
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .reduce(|mut total, partial| {
+                total.iter_mut().zip(partial).for_each(|(t, p)| *t += p);
+                total
+            })
+            .unwrap_or_else(|| vec![0.0; WEIGHT_COUNT])
+    });
+
+    let scale = -2.0 * k * 10f64.ln() / 400.0 / entries.len() as f64;
+
+    gradient.iter_mut().for_each(|g| *g *= scale);
+
+    gradient
+}
+
+/// Finds the sigmoid scaling constant that best fits the current weights
+fn find_k(entries: &[Entry], weights: &[f64]) -> f64 {
+    let mut low = 0.0;
+    let mut high = 10.0;
+
+    // Ternary search, the error is unimodal in k
+    for _ in 0..50 {
+        let a = low + (high - low) / 3.0;
+        let b = high - (high - low) / 3.0;
+
+        if error(entries, weights, a) < error(entries, weights, b) {
+            high = b;
+        } else {
+            low = a;
+        }
+    }
+
+    (low + high) / 2.0
+}
+
+fn emit_source(weights: &[f64]) -> String {
+    let rounded: Vec<i16> = weights.iter().map(|w| w.round() as i16).collect();
+
+    let mut source = String::new();
+
+    let material: Vec<String> = rounded[MATERIAL_OFFSET..MATERIAL_OFFSET + 5]
+        .iter()
+        .map(|w| w.to_string())
+        .collect();
+
+    source += "// Pawn, knight, bishop, rook, queen\n";
+    source += &format!(
+        "static MATERIAL_VALUES: [i16; 5] = [{}];\n\n",
+        material.join(", ")
+    );
+    source += &format!(
+        "static BISHOP_PAIR_VALUE: i16 = {};\n",
+        rounded[BISHOP_PAIR_OFFSET]
+    );
+
+    for (offset, suffix) in [
+        (PIECE_SQUARE_OFFSET, ""),
+        (PIECE_SQUARE_LATE_OFFSET, "_LATE"),
+    ] {
+        for (i, name) in TABLE_NAMES.iter().enumerate() {
+            let table = &rounded[offset + i * 64..offset + (i + 1) * 64];
+
+            source += &emit_table(&format!("{name}_VALUES{suffix}"), table);
+        }
+    }
+
+    source
+}
+
+fn emit_table(name: &str, table: &[i16]) -> String {
+    let width = table.iter().map(|w| w.to_string().len()).max().unwrap_or(1);
+
+    let mut source = format!("\n#[rustfmt::skip]\nstatic {name}: [i16; 64] = [\n");
+
+    for row in table.chunks(8) {
+        let values: Vec<String> = row.iter().map(|w| format!("{w:>width$}")).collect();
+
+        source += &format!("    {},\n", values.join(", "));
+    }
+
+    source += "];\n";
+
+    source
+}
@@ -1,4 +1,6 @@
 use crate::engine::Engine;
+use shakmaty::variant::{Variant, VariantPosition};
+use shakmaty::zobrist::Zobrist64;
 use shakmaty::{fen::Fen, uci::UciMove, EnPassantMode, Move, Position};
 use std::{io, str::SplitWhitespace};
 
@@ -8,9 +10,16 @@ pub fn input_loop(engine: &mut Engine) {
     loop {
         input.clear();
 
-        io::stdin()
+        // End of input is treated like quit
+        if io::stdin()
             .read_line(&mut input)
-            .expect("Failed to read line.");
+            .expect("Failed to read line.")
+            == 0
+        {
+            engine.stop();
+
+            return;
+        }
 
         let input = input.trim();
 
@@ -21,12 +30,19 @@ pub fn input_loop(engine: &mut Engine) {
                 "uci" => uci(),
                 "debug" => debug(&mut tokens, engine),
                 "isready" => isready(),
+                "setoption" => setoption(&mut tokens, engine),
                 "position" => position(&mut tokens, engine),
                 "ucinewgame" => ucinewgame(engine),
                 "go" => go(&mut tokens, engine),
                 "stop" => stop(engine),
                 "ponderhit" => ponderhit(engine),
-                "quit" => quit(),
+                #[cfg(feature = "tune")]
+                "spsa" => print!("{}", engine.spsa()),
+                "quit" => {
+                    engine.stop();
+
+                    return;
+                }
                 _ => {}
             }
         }
@@ -34,7 +50,63 @@ pub fn input_loop(engine: &mut Engine) {
 }
 
 fn uci() {
-    println!("id name Reggz\nid author tailow\nuciok")
+    println!("id name Reggz\nid author tailow");
+    println!("option name EvalFile type string default <empty>");
+    println!(
+        "option name Use NNUE type check default {}",
+        cfg!(feature = "embedded-nnue")
+    );
+    println!("option name SyzygyPath type string default <empty>");
+    println!("option name SyzygyProbeLimit type spin default 7 min 0 max 7");
+    println!(
+        "option name UCI_Variant type combo default chess{}",
+        Variant::ALL
+            .iter()
+            .map(|variant| format!(" var {}", variant.uci()))
+            .collect::<String>()
+    );
+    println!("option name UCI_Chess960 type check default false");
+    println!("option name MultiPV type spin default 1 min 1 max 64");
+    println!(
+        "option name Skill Level type spin default {0} min 0 max {0}",
+        crate::skill::MAX_LEVEL
+    );
+    println!("option name UCI_LimitStrength type check default false");
+    println!(
+        "option name UCI_Elo type spin default {1} min {0} max {1}",
+        crate::skill::MIN_ELO,
+        crate::skill::MAX_ELO
+    );
+    println!("option name Contempt type spin default 0 min -100 max 100");
+    println!("option name Analysis Contempt type check default false");
+    println!("option name OwnBook type check default false");
+    println!("option name BookFile type string default <empty>");
+    println!("option name BookDepth type spin default 20 min 0 max 200");
+    println!("option name BookBestMove type check default false");
+    println!(
+        "option name InternalIterative type combo default {}{}",
+        crate::params::InternalIterative::Reduction.name(),
+        crate::params::InternalIterative::ALL
+            .iter()
+            .map(|mode| format!(" var {}", mode.name()))
+            .collect::<String>()
+    );
+
+    #[cfg(feature = "tune")]
+    {
+        let mut params = crate::params::SearchParams::default();
+
+        for tunable in &crate::params::TUNABLES {
+            println!(
+                "option name {} type spin default {} min {} max {}",
+                tunable.name,
+                (tunable.value)(&mut params),
+                tunable.min,
+                tunable.max
+            );
+        }
+    }
+    println!("uciok");
 }
 
 fn debug(tokens: &mut SplitWhitespace<'_>, engine: &mut Engine) {
@@ -45,6 +117,25 @@ fn debug(tokens: &mut SplitWhitespace<'_>, engine: &mut Engine) {
     }
 }
 
+fn setoption(tokens: &mut SplitWhitespace<'_>, engine: &mut Engine) {
+    if tokens.next() != Some("name") {
+        return;
+    }
+
+    let mut name: Vec<&str> = Vec::new();
+    let mut value: Vec<&str> = Vec::new();
+
+    while let Some(token) = tokens.next() {
+        if token == "value" {
+            value.extend(tokens.by_ref());
+        } else {
+            name.push(token);
+        }
+    }
+
+    engine.set_option(&name.join(" "), &value.join(" "));
+}
+
 fn isready() {
     println!("readyok")
 }
@@ -53,50 +144,87 @@ fn ucinewgame(engine: &mut Engine) {
     engine.reset();
 }
 
+// The new position replaces the current one only if the FEN and every move are valid
 fn position(tokens: &mut SplitWhitespace<'_>, engine: &mut Engine) {
-    match tokens.next() {
-        Some("startpos") => engine.reset(),
+    let mut tokens = tokens.peekable();
+
+    let mut board: VariantPosition = match tokens.next() {
+        Some("startpos") => VariantPosition::new(engine.variant),
         Some("fen") => {
-            engine.reset();
+            // Variant FENs can have an extra field, such as the checks left in Three-check
+            let mut fen_parts: Vec<&str> = Vec::new();
 
-            let fen_parts: Vec<&str> = tokens.by_ref().take(6).collect();
+            while let Some(part) = tokens.next_if(|token| *token != "moves") {
+                fen_parts.push(part);
+            }
 
             let fen_string = fen_parts.join(" ");
 
             let fen: Fen = match Fen::from_ascii(fen_string.as_bytes()) {
                 Ok(fen) => fen,
-                Err(_) => return,
+                Err(error) => {
+                    println!("info string invalid fen {fen_string}: {error}");
+                    return;
+                }
             };
 
-            engine.board = match fen.into_position(shakmaty::CastlingMode::Standard) {
+            match VariantPosition::from_setup(
+                engine.variant,
+                fen.into_setup(),
+                engine.castling_mode,
+            ) {
                 Ok(board) => board,
-                Err(_) => return,
-            };
+                Err(error) => {
+                    println!("info string invalid position {fen_string}: {error}");
+                    return;
+                }
+            }
         }
-        _ => return,
-    }
+        Some(token) => {
+            println!("info string expected startpos or fen, got {token}");
+            return;
+        }
+        None => {
+            println!("info string missing position");
+            return;
+        }
+    };
 
-    if let Some("moves") = tokens.next() {
-        let moves: Vec<&str> = tokens.collect();
+    let mut position_history: Vec<Zobrist64> = vec![board.zobrist_hash(EnPassantMode::Legal)];
 
-        for uci_move_string in moves {
-            let uci_move: UciMove = match UciMove::from_ascii(uci_move_string.as_bytes()) {
-                Ok(m) => m,
-                Err(_) => return,
-            };
+    match tokens.next() {
+        Some("moves") => {
+            for uci_move_string in tokens {
+                let uci_move: UciMove = match UciMove::from_ascii(uci_move_string.as_bytes()) {
+                    Ok(m) => m,
+                    Err(_) => {
+                        println!("info string invalid move {uci_move_string}");
+                        return;
+                    }
+                };
 
-            let new_move: Move = match uci_move.to_move(&engine.board) {
-                Ok(m) => m,
-                Err(_) => return,
-            };
+                // Only legal moves convert, pseudo-legal and malformed ones are rejected
+                let new_move: Move = match uci_move.to_move(&board) {
+                    Ok(m) => m,
+                    Err(_) => {
+                        println!("info string illegal move {uci_move_string}");
+                        return;
+                    }
+                };
 
-            engine.board.play_unchecked(new_move);
+                board.play_unchecked(new_move);
 
-            engine
-                .position_history
-                .push(engine.board.zobrist_hash(EnPassantMode::Legal));
+                position_history.push(board.zobrist_hash(EnPassantMode::Legal));
+            }
+        }
+        Some(token) => {
+            println!("info string expected moves, got {token}");
+            return;
         }
+        None => {}
     }
+
+    engine.set_position(board, position_history);
 }
 
 fn go(tokens: &mut SplitWhitespace<'_>, engine: &mut Engine) {
@@ -106,6 +234,7 @@ fn go(tokens: &mut SplitWhitespace<'_>, engine: &mut Engine) {
     let mut binc: Option<u64> = None;
     let mut movetime: Option<u64> = None;
     let mut depth: Option<i16> = None;
+    let mut nodes: Option<u64> = None;
     let mut infinite = false;
 
     while let Some(token) = tokens.next() {
@@ -116,12 +245,13 @@ fn go(tokens: &mut SplitWhitespace<'_>, engine: &mut Engine) {
             "binc" => binc = tokens.next().and_then(|v| v.parse().ok()),
             "movetime" => movetime = tokens.next().and_then(|v| v.parse().ok()),
             "depth" => depth = tokens.next().and_then(|v| v.parse().ok()),
+            "nodes" => nodes = tokens.next().and_then(|v| v.parse().ok()),
             "infinite" => infinite = true,
             _ => {}
         }
     }
 
-    engine.search(wtime, btime, winc, binc, movetime, depth, infinite);
+    engine.search(wtime, btime, winc, binc, movetime, depth, nodes, infinite);
 }
 
 fn stop(engine: &mut Engine) {
@@ -132,6 +262,173 @@ fn ponderhit(engine: &mut Engine) {
     engine.ponder_hit();
 }
 
-fn quit() {
-    std::process::exit(0);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::{Bitboard, Board, CastlingMode, CastlingSide, Color, File, Rank, Role, Square};
+
+    // Every Chess960 start position parses with castling rights on both rooks, and castling
+    // from it, with only the kings, the castling rook and the pawns left, round trips as king
+    // takes rook
+    #[test]
+    fn chess960_start_positions() {
+        let mut engine = Engine::new();
+
+        engine.set_option("UCI_Chess960", "true");
+
+        assert_eq!(chess960(518), Board::default());
+        assert_eq!(
+            chess960(0),
+            "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR"
+                .parse()
+                .unwrap()
+        );
+        assert_eq!(
+            chess960(959),
+            "rkrnnqbb/pppppppp/8/8/8/8/PPPPPPPP/RKRNNQBB"
+                .parse()
+                .unwrap()
+        );
+
+        for number in 0..960 {
+            let board = chess960(number);
+
+            let rights: String = (board.rooks() & Bitboard::from_rank(Rank::First))
+                .into_iter()
+                .map(|square| square.file().upper_char())
+                .chain(
+                    (board.rooks() & Bitboard::from_rank(Rank::Eighth))
+                        .into_iter()
+                        .map(|square| square.file().char()),
+                )
+                .collect();
+
+            set_position(&mut engine, &format!("fen {board} w {rights} - 0 1"));
+
+            assert_eq!(engine.board.board(), &board, "position {number}");
+
+            for color in Color::ALL {
+                for side in [CastlingSide::KingSide, CastlingSide::QueenSide] {
+                    assert!(engine.board.castles().has(color, side), "position {number}");
+                }
+            }
+
+            let back_ranks = Bitboard::from_rank(Rank::First) | Bitboard::from_rank(Rank::Eighth);
+
+            for color in Color::ALL {
+                let king = board.king_of(color).unwrap();
+
+                for rook in board.rooks() & board.by_color(color) {
+                    let uci = format!("{king}{rook}");
+
+                    // Only the kings and the castling rook are left on the back ranks, so the
+                    // other rook can't stand on the castling squares
+                    let mut stripped = board.clone();
+
+                    for square in board.occupied() & back_ranks {
+                        if !board.kings().contains(square) && square != rook {
+                            stripped.discard_piece_at(square);
+                        }
+                    }
+
+                    let rights = match color {
+                        Color::White => rook.file().upper_char(),
+                        Color::Black => rook.file().char(),
+                    };
+
+                    let fen = format!("fen {stripped} {} {rights} - 0 1", color.fold_wb("w", "b"));
+
+                    set_position(&mut engine, &fen);
+
+                    let m = UciMove::from_ascii(uci.as_bytes())
+                        .unwrap()
+                        .to_move(&engine.board)
+                        .unwrap_or_else(|_| panic!("position {number} {uci}"));
+
+                    assert!(m.is_castle(), "position {number} {uci}");
+                    assert_eq!(m.to_uci(CastlingMode::Chess960).to_string(), uci);
+
+                    set_position(&mut engine, &format!("{fen} moves {uci}"));
+
+                    let (king_file, rook_file) = if rook.file() > king.file() {
+                        (File::G, File::F)
+                    } else {
+                        (File::C, File::D)
+                    };
+
+                    let rank = color.fold_wb(Rank::First, Rank::Eighth);
+
+                    assert_eq!(
+                        engine.board.board().king_of(color),
+                        Some(Square::from_coords(king_file, rank)),
+                        "position {number} {uci}"
+                    );
+                    assert_eq!(
+                        engine
+                            .board
+                            .board()
+                            .role_at(Square::from_coords(rook_file, rank)),
+                        Some(Role::Rook),
+                        "position {number} {uci}"
+                    );
+                }
+            }
+        }
+    }
+
+    // Start position of a Scharnagl number, 518 being the standard one
+    fn chess960(number: u32) -> Board {
+        let mut back_rank: [Option<Role>; 8] = [None; 8];
+
+        let mut n = number as usize;
+
+        back_rank[n % 4 * 2 + 1] = Some(Role::Bishop);
+        n /= 4;
+        back_rank[n % 4 * 2] = Some(Role::Bishop);
+        n /= 4;
+
+        let mut place = |role: Role, index: usize| {
+            let file = (0..8)
+                .filter(|&file| back_rank[file].is_none())
+                .nth(index)
+                .unwrap();
+
+            back_rank[file] = Some(role);
+        };
+
+        place(Role::Queen, n % 6);
+        n /= 6;
+
+        let knights = [
+            (0, 1),
+            (0, 2),
+            (0, 3),
+            (0, 4),
+            (1, 2),
+            (1, 3),
+            (1, 4),
+            (2, 3),
+            (2, 4),
+            (3, 4),
+        ];
+        let (first, second) = knights[n];
+
+        // The second knight goes to the remaining empty squares after the first is placed
+        place(Role::Knight, first);
+        place(Role::Knight, second - 1);
+
+        place(Role::Rook, 0);
+        place(Role::King, 0);
+        place(Role::Rook, 0);
+
+        let rank: String = back_rank.iter().map(|role| role.unwrap().char()).collect();
+
+        format!("{rank}/pppppppp/8/8/8/8/PPPPPPPP/{}", rank.to_uppercase())
+            .parse()
+            .unwrap()
+    }
+
+    fn set_position(engine: &mut Engine, command: &str) {
+        position(&mut command.split_whitespace(), engine);
+    }
 }
@@ -0,0 +1,125 @@
+use crate::evaluate::{evaluate, evaluate_board, material_value};
+use shakmaty::variant::{
+    Antichess, Atomic, Crazyhouse, Horde, KingOfTheHill, RacingKings, ThreeCheck,
+};
+use shakmaty::{Chess, Color, Position, Role};
+
+/// A position type the search can play, with the evaluation hooks of its variant
+pub trait SearchPosition: Position + Clone + Send + 'static {
+    /// Static evaluation from white's perspective
+    fn evaluate(&self) -> i16 {
+        evaluate_board(self.board())
+    }
+
+    /// The position as standard chess, for the evaluation network, tablebases and books
+    fn as_chess(&self) -> Option<&Chess> {
+        None
+    }
+}
+
+impl SearchPosition for Chess {
+    fn evaluate(&self) -> i16 {
+        evaluate(self)
+    }
+
+    fn as_chess(&self) -> Option<&Chess> {
+        Some(self)
+    }
+}
+
+impl SearchPosition for Atomic {}
+
+impl SearchPosition for Horde {}
+
+impl SearchPosition for Antichess {
+    // Losing pieces is the goal
+    fn evaluate(&self) -> i16 {
+        let board = self.board();
+
+        let mut score: i16 = 0;
+
+        for (_, piece) in board {
+            let value = material_value(piece.role).max(100);
+
+            score += piece.color.fold_wb(-value, value);
+        }
+
+        score
+    }
+}
+
+impl SearchPosition for KingOfTheHill {
+    // Closer to the centre is better, the game is won on it
+    fn evaluate(&self) -> i16 {
+        let mut score = evaluate_board(self.board());
+
+        for color in Color::ALL {
+            if let Some(king) = self.board().king_of(color) {
+                let file = king.file() as i16;
+                let rank = king.rank() as i16;
+
+                let distance = (2 * file - 7).abs().max((2 * rank - 7).abs()) / 2;
+
+                score += color.fold_wb(1, -1) * (3 - distance) * 60;
+            }
+        }
+
+        score
+    }
+}
+
+// Bonus for the checks given so far, three checks end the game
+static CHECK_VALUES: [i16; 3] = [0, 150, 450];
+
+impl SearchPosition for ThreeCheck {
+    fn evaluate(&self) -> i16 {
+        let mut score = evaluate_board(self.board());
+
+        if let Some(remaining_checks) = self.remaining_checks() {
+            for color in Color::ALL {
+                let remaining = u32::from(*remaining_checks.get(color)).min(3) as usize;
+                let given = 3 - remaining;
+
+                if given < 3 {
+                    score += color.fold_wb(1, -1) * CHECK_VALUES[given];
+                }
+            }
+        }
+
+        score
+    }
+}
+
+impl SearchPosition for Crazyhouse {
+    // Pieces in hand can be dropped anywhere, so they keep their full value
+    fn evaluate(&self) -> i16 {
+        let mut score = evaluate_board(self.board());
+
+        if let Some(pockets) = self.pockets() {
+            for color in Color::ALL {
+                for role in Role::ALL {
+                    let count = *pockets.get(color).get(role) as i16;
+
+                    score += color.fold_wb(1, -1) * count * material_value(role);
+                }
+            }
+        }
+
+        score
+    }
+}
+
+impl SearchPosition for RacingKings {
+    // Both kings race to the eighth rank
+    fn evaluate(&self) -> i16 {
+        let mut score = evaluate_board(self.board());
+
+        for color in Color::ALL {
+            if let Some(king) = self.board().king_of(color) {
+                score += color.fold_wb(1, -1) * king.rank() as i16 * 80;
+            }
+        }
+
+        score
+    }
+}